@@ -2,22 +2,39 @@ mod models;
 
 use crate::models::{
     archive::ArchiveOrg,
-    article::Article,
-    episode::Episode,
-    page::Page,
+    article,
+    chapters,
+    content::{self, Document, Schema},
+    data::Data,
+    episode::{self, Episode},
+    files,
+    markdown::{self, Renderer},
+    person::{self, Credit, Person},
+    related,
+    stats::Statistics,
+    theme,
+    page,
     site::{Layout, Post},
+    transcript::Transcript,
+    webhook::{self, Event},
 };
 use minijinja::context;
 use models::{
     config::Configuration,
-    mastodon::{get_mastodon_client, Mastodon},
-    telegram::{get_telegram_client, Telegram},
+    announcer::{self, Announcer},
     ENV,
 };
-use std::str::FromStr;
-use tracing::{debug, error, info};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 const VERSION: usize = 1;
+/// Days after publication an episode is checked on archive.org for a
+/// transcript or artwork uploaded later, so older ones don't cost a
+/// request on every run
+const BACKFILL_DAYS: i64 = 30;
 
 #[tokio::main]
 async fn main() {
@@ -31,18 +48,55 @@ async fn main() {
 
 
     debug!("Configuration: {:?}", configuration);
+    set_theme(configuration.get_theme()).await;
+    let _ = markdown::OPTIONS.set(configuration.get_markdown().clone());
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if matches!(args.first().map(|arg| arg.as_str()), Some("fmt") | Some("fix")) {
+        fix(args.iter().any(|arg| arg == "--check")).await;
+        return;
+    }
+    // only the statistics page and its exports, from the local data
+    if args.first().map(|arg| arg.as_str()) == Some("stats") {
+        let posts = read_episodes_and_posts(false).await;
+        // the page links the published style sheet
+        generate_assets(&configuration).await;
+        generate_stats(&configuration, &posts).await;
+        return;
+    }
 
-    update(&configuration).await;
+    // previews include drafts and scheduled content and never sync nor announce
+    let drafts = args.iter().any(|arg| arg == "--drafts");
+    if drafts {
+        info!("Preview mode. Drafts and scheduled content included");
+    } else {
+        update(&configuration).await;
+    }
 
-    let posts = read_episodes_and_posts().await;
-    let pages = read_pages().await;
+    let mut posts = read_episodes_and_posts(drafts).await;
+    let mut pages = read_pages(drafts).await;
+    let people = read_people(&configuration).await;
+    attach_people(&mut posts, &people);
+    render_content(&configuration, &mut posts, &mut pages);
+    related::link(&mut posts);
     debug!("{:?}", posts);
     debug!("=== Generation ===");
     create_public(&configuration).await;
+    generate_assets(&configuration).await;
     generate_html(&configuration, &posts, &pages).await;
     generate_index(&configuration, &posts, &pages).await;
-    generate_feed(&configuration, &posts).await;
+    generate_search(&configuration, &posts, &pages).await;
+    generate_seasons(&configuration, &posts, &pages).await;
+    generate_people(&configuration, &posts, &pages, &people).await;
+    generate_feed(&configuration, &posts, &people).await;
     generate_stats(&configuration, &posts).await;
+    if !drafts {
+        webhook::notify(configuration.get_webhooks(), Event::BuildFinished, &configuration.get_site().url, None).await;
+    }
+}
+
+async fn generate_assets(configuration: &Configuration) {
+    debug!("generate_assets");
     let public = if configuration.get_site().baseurl.is_empty() {
         configuration.get_public().to_owned()
     } else {
@@ -52,88 +106,309 @@ async fn main() {
             configuration.get_site().baseurl
         )
     };
-    //TODO: Copy directory assets a /public/{podcast}/assets
-    //let output = format!("{}/style.css", public);
     let assets_dir = format!("{}/assets", public);
-    create_dir(&assets_dir).await;
-    copy_all_files("assets", &assets_dir).await;
+    match configuration
+        .get_assets()
+        .build(configuration.get_style_css(), &assets_dir)
+        .await
+    {
+        Ok(manifest) => debug!("Assets: {:?}", manifest),
+        Err(err) => {
+            error!("Can not build assets. {:#}", err);
+            let mut err = &err as &dyn std::error::Error;
+            while let Some(next_err) = err.source() {
+                error!("caused by: {:#}", next_err);
+                err = next_err;
+            }
+            std::process::exit(1);
+        }
+    }
 }
 
-async fn read_pages() -> Vec<Post> {
+async fn read_posts<S: Schema>(drafts: bool) -> Vec<Post> {
     let mut posts = Vec::new();
-    let mut posts_dir = tokio::fs::read_dir("pages").await.unwrap();
-    while let Some(file) = posts_dir.next_entry().await.unwrap() {
-        if file.metadata().await.unwrap().is_file() {
-            let filename = file.file_name().to_str().unwrap().to_string();
-            if filename.ends_with(".md") {
-                match Page::new(&filename).await {
-                    Ok(page) => posts.push(page.get_post()),
-                    Err(err) => {
-                        error!("Can not write {}. {:#}", filename, err);
-                        // render causes as well
-                        let mut err = &err as &dyn std::error::Error;
-                        while let Some(next_err) = err.source() {
-                            error!("caused by: {:#}", next_err);
-                            err = next_err;
-                        }
-                    }
+    let filenames = match content::list::<S>().await {
+        Ok(filenames) => filenames,
+        Err(err) => {
+            error!("Can not read {}. {:#}", S::DIR, err);
+            // render causes as well
+            let mut err = &err as &dyn std::error::Error;
+            while let Some(next_err) = err.source() {
+                error!("caused by: {:#}", next_err);
+                err = next_err;
+            }
+            return posts;
+        }
+    };
+    for filename in filenames {
+        debug!("Read {}: {}", S::DIR, filename);
+        let path = format!("{}/{}", S::DIR, filename);
+        match Document::<S>::new(&filename)
+            .await
+            .and_then(|document| document.check_date(&path))
+        {
+            Ok(document) => posts.push(document.get_post()),
+            Err(err) => {
+                error!("Can not read {}. {:#}", filename, err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
                 }
             }
         }
     }
-    posts.sort_by(|a, b| b.date.cmp(&a.date));
+    posts.retain(|post| drafts || post.is_published());
     posts
 }
 
-async fn read_episodes_and_posts() -> Vec<Post> {
-    let mut posts = Vec::new();
-    let mut episodes_dir = tokio::fs::read_dir("episodes").await.unwrap();
-    while let Some(file) = episodes_dir.next_entry().await.unwrap() {
-        if file.metadata().await.unwrap().is_file() {
-            let filename = file.file_name().to_str().unwrap().to_string();
-            if filename.ends_with(".md") {
-                debug!("Read episode: {}", filename);
-                match Episode::new(&filename).await {
-                    Ok(episode) => posts.push(episode.get_post()),
-                    Err(err) => {
-                        error!("Can not write {}. {:#}", filename, err);
-                        // render causes as well
-                        let mut err = &err as &dyn std::error::Error;
-                        while let Some(next_err) = err.source() {
-                            error!("caused by: {:#}", next_err);
-                            err = next_err;
-                        }
-                    }
+/// Fills in missing slugs and excerpts in the sources. With `check` it only
+/// lists the sources that need it and fails if there is any.
+async fn fix(check: bool) {
+    let mut pending = fix_all::<episode::Metadata>(check).await;
+    pending += fix_all::<article::Metadata>(check).await;
+    pending += fix_all::<page::Metadata>(check).await;
+    if check && pending > 0 {
+        error!("{} files need fmt", pending);
+        std::process::exit(1);
+    }
+}
+
+async fn fix_all<S: Schema>(check: bool) -> usize {
+    let mut pending = 0;
+    let filenames = match content::list::<S>().await {
+        Ok(filenames) => filenames,
+        Err(err) => {
+            error!("Can not read {}. {:#}", S::DIR, err);
+            return pending;
+        }
+    };
+    for filename in filenames {
+        match content::fix::<S>(&filename, check).await {
+            Ok(Some(target)) => {
+                pending += 1;
+                if check {
+                    info!("{}/{} needs fmt", S::DIR, filename);
+                } else {
+                    info!("{}/{} fixed as {}", S::DIR, filename, target);
+                }
+            }
+            Ok(None) => debug!("{}/{} is fine", S::DIR, filename),
+            Err(err) => {
+                pending += 1;
+                error!("Can not fix {}. {:#}", filename, err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
                 }
             }
         }
     }
+    pending
+}
 
-    let mut posts_dir = tokio::fs::read_dir("posts").await.unwrap();
-    while let Some(file) = posts_dir.next_entry().await.unwrap() {
-        if file.metadata().await.unwrap().is_file() {
-            let filename = file.file_name().to_str().unwrap().to_string();
-            if filename.ends_with(".md") {
-                match Article::new(&filename).await {
-                    Ok(article) => posts.push(article.get_post()),
-                    Err(err) => {
-                        error!("Can not write {}. {:#}", filename, err);
-                        // render causes as well
-                        let mut err = &err as &dyn std::error::Error;
-                        while let Some(next_err) = err.source() {
-                            error!("caused by: {:#}", next_err);
-                            err = next_err;
-                        }
-                    }
+/// Templates missing from `templates/` come from the theme
+async fn set_theme(name: &str) {
+    let dir = format!("{}/{}", theme::THEMES_DIR, name);
+    if name != theme::DEFAULT_THEME && tokio::fs::metadata(&dir).await.is_err() {
+        warn!("Theme {} not found in {}. Using the default theme", name, dir);
+    }
+    info!("Theme {}", name);
+    let _ = theme::THEME.set(name.to_string());
+}
+
+/// People in `people/` and in the configuration. A file wins over an
+/// entry of the configuration with the same slug.
+async fn read_people(configuration: &Configuration) -> Vec<Person> {
+    let mut people: Vec<Person> = configuration
+        .get_people()
+        .iter()
+        .map(|person| person.get_person())
+        .collect();
+    people.sort_by(|a, b| a.name.cmp(&b.name));
+    if tokio::fs::metadata(person::Metadata::DIR).await.is_err() {
+        debug!("No {} directory", person::Metadata::DIR);
+        return people;
+    }
+    let filenames = match content::list::<person::Metadata>().await {
+        Ok(filenames) => filenames,
+        Err(err) => {
+            error!("Can not read {}. {:#}", person::Metadata::DIR, err);
+            return people;
+        }
+    };
+    for filename in filenames {
+        match Document::<person::Metadata>::new(&filename).await {
+            Ok(document) => {
+                let person = document.get_person();
+                people.retain(|other| other.slug != person.slug);
+                people.push(person);
+            }
+            Err(err) => {
+                error!("Can not read {}. {:#}", filename, err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
                 }
             }
         }
     }
-    posts.sort_by(|a, b| b.date.cmp(&a.date));
+    people.sort_by(|a, b| a.name.cmp(&b.name));
+    people
+}
+
+/// Renders the Markdown of posts and pages, shortcodes included, now that
+/// all of them can be looked up
+fn render_content(configuration: &Configuration, posts: &mut [Post], pages: &mut [Post]) {
+    let url = if configuration.get_site().baseurl.is_empty() {
+        "".to_string()
+    } else if configuration.get_site().baseurl.starts_with('/') {
+        configuration.get_site().baseurl.to_owned()
+    } else {
+        format!("/{}", configuration.get_site().baseurl)
+    };
+    let mut all = posts.to_vec();
+    all.extend(pages.iter().cloned());
+    let renderer = Renderer::new(&url, configuration.get_site(), &all);
+    for post in posts.iter_mut().chain(pages.iter_mut()) {
+        post.content = renderer.render(&post.source);
+        if configuration.get_markdown().toc {
+            post.toc = markdown::toc(&post.content);
+        }
+    }
+}
+
+/// Completes the people credited in every post with their name and avatar
+fn attach_people(posts: &mut [Post], people: &[Person]) {
+    for post in posts.iter_mut() {
+        for credit in post.people.iter_mut() {
+            match people.iter().find(|person| person.slug == credit.slug) {
+                Some(person) => credit.fill(person),
+                None => warn!("{} credits unknown person {}", post.slug, credit.slug),
+            }
+        }
+    }
+}
+
+fn get_hosts(configuration: &Configuration, people: &[Person]) -> Vec<Credit> {
+    configuration
+        .get_site()
+        .hosts
+        .iter()
+        .filter_map(|slug| people.iter().find(|person| &person.slug == slug))
+        .map(Credit::host)
+        .collect()
+}
+
+async fn read_pages(drafts: bool) -> Vec<Post> {
+    let mut posts = read_posts::<page::Metadata>(drafts).await;
+    posts.sort_by_key(|post| std::cmp::Reverse(post.date));
     posts
 }
 
-async fn post_with_mastodon(configuration: &Configuration, episode: &Episode, mastodon: &Mastodon) {
+async fn read_episodes_and_posts(drafts: bool) -> Vec<Post> {
+    let mut posts = read_posts::<episode::Metadata>(drafts).await;
+    for post in posts.iter_mut() {
+        post.transcript = Transcript::read(episode::Metadata::DIR, &post.identifier).await;
+    }
+    posts.extend(read_posts::<article::Metadata>(drafts).await);
+    posts.sort_by_key(|post| std::cmp::Reverse(post.date));
+    posts
+}
+
+/// Queues the announcement of `episode` in every configured channel it has
+/// not been announced in yet
+fn queue_announcements(data: &mut Data, episode: &Episode, announcers: &[Box<dyn Announcer>]) {
+    let now = chrono::Utc::now();
+    for announcer in announcers {
+        if !episode.is_announced_in(announcer.channel()) {
+            data.outbox.enqueue(episode.get_identifier(), announcer.channel(), now);
+        }
+    }
+}
+
+/// Sends the announcements that are due. Sent ones are recorded in the
+/// episode so they are never sent twice, failed ones wait for a later run
+async fn send_announcements(
+    configuration: &Configuration,
+    data: &mut Data,
+    announcers: &[Box<dyn Announcer>],
+) {
+    let now = chrono::Utc::now();
+    for announcement in data.outbox.due(now) {
+        let filename = format!("{}.md", announcement.identifier);
+        let mut episode = match Episode::new(&filename).await {
+            Ok(episode) => episode,
+            Err(err) => {
+                error!("Can not announce {}. {:#}", announcement.identifier, err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
+                }
+                if data.outbox.fail(&announcement, now) {
+                    info!("Announcement of {} in {} will be retried", announcement.identifier, announcement.channel);
+                }
+                continue;
+            }
+        };
+        if episode.is_announced_in(&announcement.channel) {
+            data.outbox.remove(&announcement);
+            continue;
+        }
+        let announcer = match announcers
+            .iter()
+            .find(|announcer| announcer.channel() == announcement.channel)
+        {
+            Some(announcer) => announcer,
+            None => {
+                // it is sent once the channel is configured again
+                warn!("Channel {} is not configured", announcement.channel);
+                continue;
+            }
+        };
+        match announcer.announce(configuration.get_site(), &get_episode_post(&episode)).await {
+            Ok(id) => {
+                info!("Episode {} announced in {}: {}", episode.get_slug(), announcement.channel, id);
+                data.outbox.remove(&announcement);
+                episode.set_announcement(&announcement.channel, &id);
+                if let Err(err) = episode.save_fields(&["announcements"]).await {
+                    error!("Can not save episode {}. {:#}", episode.get_slug(), err);
+                }
+            }
+            Err(err) => {
+                error!("Can not announce {}. {:#}", episode.get_slug(), err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
+                }
+                if data.outbox.fail(&announcement, now) {
+                    info!("Announcement of {} in {} will be retried", episode.get_slug(), announcement.channel);
+                }
+            }
+        }
+    }
+}
+
+async fn generate_feed(configuration: &Configuration, posts: &[Post], people: &[Person]) {
+    debug!("generate_feed");
+    let public = if configuration.get_site().baseurl.is_empty() {
+        configuration.get_public().to_owned()
+    } else {
+        format!(
+            "{}/{}",
+            configuration.get_public(),
+            configuration.get_site().baseurl
+        )
+    };
     let url = if configuration.get_site().baseurl.is_empty() {
         "".to_string()
     } else if configuration.get_site().baseurl.starts_with('/') {
@@ -141,19 +416,30 @@ async fn post_with_mastodon(configuration: &Configuration, episode: &Episode, ma
     } else {
         format!("/{}", configuration.get_site().baseurl)
     };
+    let filter_posts: Vec<&Post> = posts
+        .iter()
+        .filter(|post| post.layout == Layout::PODCAST && post.is_published())
+        .collect();
     let ctx = context! {
         url => url,
         site => configuration.get_site(),
-        post => episode.get_post(),
+        posts => filter_posts,
+        hosts => get_hosts(configuration, people),
     };
-    let template = ENV.get_template("mastodon.html").unwrap();
+    let template = ENV.get_template("feed.xml").unwrap();
     match template.render(ctx) {
         Ok(content) => {
-            debug!("{}", content);
-            mastodon.post(&content).await;
+            write_post(
+                &public,
+                "",
+                Some(&configuration.get_site().podcast_feed),
+                &content,
+            )
+            .await;
+            debug!("write feed");
         }
         Err(err) => {
-            error!("Algo no ha funcionado correctamente. {:#}", err);
+            error!("Could not render template: {:#}", err);
             // render causes as well
             let mut err = &err as &dyn std::error::Error;
             while let Some(next_err) = err.source() {
@@ -164,7 +450,17 @@ async fn post_with_mastodon(configuration: &Configuration, episode: &Episode, ma
     }
 }
 
-async fn post_with_telegram(configuration: &Configuration, episode: &Episode, telegram: &Telegram) {
+async fn generate_seasons(configuration: &Configuration, posts: &[Post], pages: &Vec<Post>) {
+    debug!("generate_seasons");
+    let public = if configuration.get_site().baseurl.is_empty() {
+        configuration.get_public().to_owned()
+    } else {
+        format!(
+            "{}/{}",
+            configuration.get_public(),
+            configuration.get_site().baseurl
+        )
+    };
     let url = if configuration.get_site().baseurl.is_empty() {
         "".to_string()
     } else if configuration.get_site().baseurl.starts_with('/') {
@@ -172,36 +468,83 @@ async fn post_with_telegram(configuration: &Configuration, episode: &Episode, te
     } else {
         format!("/{}", configuration.get_site().baseurl)
     };
-    let post = episode.get_post();
-    let audio = format!(
-        "https://archive.org/download/{}/{}",
-        &post.identifier, &post.filename
-    );
+    let mut seasons: BTreeMap<usize, Vec<&Post>> = BTreeMap::new();
+    for post in posts.iter().filter(|post| post.layout == Layout::PODCAST) {
+        if let Some(season) = post.season {
+            seasons.entry(season).or_default().push(post);
+        }
+    }
+    let numbers: Vec<usize> = seasons.keys().copied().collect();
+    for (season, episodes) in seasons.iter_mut() {
+        episodes.sort_by_key(|post| (post.episode, post.date));
+        let ctx = context! {
+            url => url,
+            site => configuration.get_site(),
+            pages => pages,
+            season => season,
+            seasons => numbers,
+            posts => episodes,
+        };
+        let template = ENV.get_template("season.html").unwrap();
+        match template.render(ctx) {
+            Ok(content) => {
+                let endpoint = format!("season/{}", season);
+                create_dir(&format!("{}/{}", public, endpoint)).await;
+                write_post(&public, &endpoint, None, &content).await;
+            }
+            Err(err) => {
+                error!("Could not render template: {:#}", err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
+                }
+            }
+        }
+    }
+}
+
+async fn generate_people(
+    configuration: &Configuration,
+    posts: &[Post],
+    pages: &Vec<Post>,
+    people: &[Person],
+) {
+    debug!("generate_people");
+    if people.is_empty() {
+        return;
+    }
+    let public = if configuration.get_site().baseurl.is_empty() {
+        configuration.get_public().to_owned()
+    } else {
+        format!(
+            "{}/{}",
+            configuration.get_public(),
+            configuration.get_site().baseurl
+        )
+    };
+    let url = if configuration.get_site().baseurl.is_empty() {
+        "".to_string()
+    } else if configuration.get_site().baseurl.starts_with('/') {
+        configuration.get_site().baseurl.to_owned()
+    } else {
+        format!("/{}", configuration.get_site().baseurl)
+    };
+    create_dir(&format!("{}/people", public)).await;
+    let hosts = get_hosts(configuration, people);
     let ctx = context! {
         url => url,
         site => configuration.get_site(),
-        audio => audio,
-        post => episode.get_post(),
+        pages => pages,
+        hosts => hosts,
+        people => people,
     };
-    let template = ENV.get_template("telegram.html").unwrap();
+    let template = ENV.get_template("people.html").unwrap();
     match template.render(ctx) {
-        Ok(caption) => {
-            info!("Caption: {caption}");
-            match telegram.send_audio(&audio, &caption).await{
-                Ok(result) => info!("Message send to Telegram: {result}"),
-                Err(err) => {
-                    error!("Can not sent message to Telegram. Caption: {}. Error: {:#}", &caption, err);
-                    // render causes as well
-                    let mut err = &err as &dyn std::error::Error;
-                    while let Some(next_err) = err.source() {
-                        error!("caused by: {:#}", next_err);
-                        err = next_err;
-                    }
-                }
-            }
-        }
+        Ok(content) => write_post(&public, "people", None, &content).await,
         Err(err) => {
-            error!("Algo no ha funcionado correctamente. {:#}", err);
+            error!("Could not render template: {:#}", err);
             // render causes as well
             let mut err = &err as &dyn std::error::Error;
             while let Some(next_err) = err.source() {
@@ -210,10 +553,47 @@ async fn post_with_telegram(configuration: &Configuration, episode: &Episode, te
             }
         }
     }
+    for person in people {
+        let appearances: Vec<(&Post, &Credit)> = posts
+            .iter()
+            .filter_map(|post| {
+                post.people
+                    .iter()
+                    .find(|credit| credit.slug == person.slug)
+                    .map(|credit| (post, credit))
+            })
+            .collect();
+        let ctx = context! {
+            url => url,
+            site => configuration.get_site(),
+            pages => pages,
+            person => person,
+            host => hosts.iter().any(|host| host.slug == person.slug),
+            posts => appearances.iter().map(|(post, _)| post).collect::<Vec<_>>(),
+            roles => appearances.iter().map(|(_, credit)| &credit.role).collect::<Vec<_>>(),
+        };
+        let template = ENV.get_template("person.html").unwrap();
+        match template.render(ctx) {
+            Ok(content) => {
+                let endpoint = format!("people/{}", person.slug);
+                create_dir(&format!("{}/{}", public, endpoint)).await;
+                write_post(&public, &endpoint, None, &content).await;
+            }
+            Err(err) => {
+                error!("Could not render template: {:#}", err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
+                }
+            }
+        }
+    }
 }
 
-async fn generate_feed(configuration: &Configuration, posts: &[Post]) {
-    debug!("generate_feed");
+async fn generate_stats(configuration: &Configuration, posts: &Vec<Post>) {
+    debug!("generate_stats");
     let public = if configuration.get_site().baseurl.is_empty() {
         configuration.get_public().to_owned()
     } else {
@@ -230,26 +610,31 @@ async fn generate_feed(configuration: &Configuration, posts: &[Post]) {
     } else {
         format!("/{}", configuration.get_site().baseurl)
     };
-    let filter_posts: Vec<&Post> = posts
-        .iter()
-        .filter(|post| post.layout == Layout::PODCAST)
-        .collect();
+    let data = match Data::read(configuration.get_data()).await {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Can not read {}. {:#}", configuration.get_data(), err);
+            Data::default()
+        }
+    };
+    let stats = Statistics::new(posts, &data, chrono::Utc::now());
     let ctx = context! {
         url => url,
         site => configuration.get_site(),
-        posts => filter_posts,
+        posts => posts,
+        stats => stats,
     };
-    let template = ENV.get_template("feed.xml").unwrap();
+    let template = ENV.get_template("statistics.html").unwrap();
     match template.render(ctx) {
         Ok(content) => {
-            write_post(
-                &public,
-                "",
-                Some(&configuration.get_site().podcast_feed),
-                &content,
-            )
-            .await;
-            debug!("write feed");
+            debug!("{}", content);
+            create_dir(&format!("{}/{}", public, "statistics")).await;
+            write_post(&public, "statistics", None, &content).await;
+            write_post(&public, "statistics", Some("stats.csv"), &stats.to_csv()).await;
+            match stats.to_json() {
+                Ok(json) => write_post(&public, "statistics", Some("stats.json"), &json).await,
+                Err(err) => error!("Can not export statistics. {:#}", err),
+            }
         }
         Err(err) => {
             error!("Could not render template: {:#}", err);
@@ -263,8 +648,8 @@ async fn generate_feed(configuration: &Configuration, posts: &[Post]) {
     }
 }
 
-async fn generate_stats(configuration: &Configuration, posts: &Vec<Post>) {
-    debug!("generate_stats");
+async fn generate_index(configuration: &Configuration, posts: &Vec<Post>, pages: &Vec<Post>) {
+    debug!("generate_index");
     let public = if configuration.get_site().baseurl.is_empty() {
         configuration.get_public().to_owned()
     } else {
@@ -284,14 +669,14 @@ async fn generate_stats(configuration: &Configuration, posts: &Vec<Post>) {
     let ctx = context! {
         url => url,
         site => configuration.get_site(),
+        pages => pages,
         posts => posts,
     };
-    let template = ENV.get_template("statistics.html").unwrap();
+    let template = ENV.get_template("index.html").unwrap();
     match template.render(ctx) {
         Ok(content) => {
             debug!("{}", content);
-            create_dir(&format!("{}/{}", public, "statistics")).await;
-            write_post(&public, "statistics", None, &content).await;
+            write_post(&public, "", None, &content).await;
         }
         Err(err) => {
             error!("Could not render template: {:#}", err);
@@ -305,8 +690,12 @@ async fn generate_stats(configuration: &Configuration, posts: &Vec<Post>) {
     }
 }
 
-async fn generate_index(configuration: &Configuration, posts: &Vec<Post>, pages: &Vec<Post>) {
-    debug!("generate_index");
+async fn generate_search(configuration: &Configuration, posts: &[Post], pages: &Vec<Post>) {
+    debug!("generate_search");
+    let search = configuration.get_search();
+    if !search.enabled {
+        return;
+    }
     let public = if configuration.get_site().baseurl.is_empty() {
         configuration.get_public().to_owned()
     } else {
@@ -323,17 +712,25 @@ async fn generate_index(configuration: &Configuration, posts: &Vec<Post>, pages:
     } else {
         format!("/{}", configuration.get_site().baseurl)
     };
+    let mut posts_and_pages = Vec::new();
+    posts_and_pages.extend_from_slice(posts);
+    posts_and_pages.extend_from_slice(pages);
+    let index = search.build(&posts_and_pages, &configuration.get_site().language);
+    match serde_json::to_string(&index) {
+        Ok(content) => write_post(&public, "", Some("search.json"), &content).await,
+        Err(err) => error!("Can not create search index. {:#}", err),
+    }
     let ctx = context! {
         url => url,
         site => configuration.get_site(),
         pages => pages,
-        posts => posts,
+        stem => search.stem,
     };
-    let template = ENV.get_template("index.html").unwrap();
+    let template = ENV.get_template("search.html").unwrap();
     match template.render(ctx) {
         Ok(content) => {
-            debug!("{}", content);
-            write_post(&public, "", None, &content).await;
+            create_dir(&format!("{}/search", public)).await;
+            write_post(&public, "search", None, &content).await;
         }
         Err(err) => {
             error!("Could not render template: {:#}", err);
@@ -381,7 +778,14 @@ async fn generate_html(configuration: &Configuration, posts: &[Post], pages: &Ve
                 debug!("{}", &content);
                 debug!("Post: {:?}", &post);
                 create_dir(&format!("{}/{}", public, &post.slug)).await;
-                write_post(&public, &post.slug, None, &content).await
+                write_post(&public, &post.slug, None, &content).await;
+                if let Some(transcript) = &post.transcript {
+                    write_post(&public, &post.slug, Some(&transcript.filename), &transcript.source).await;
+                }
+                if !post.chapters.is_empty() {
+                    let chapters = chapters::to_json(&post.chapters).to_string();
+                    write_post(&public, &post.slug, Some("chapters.json"), &chapters).await;
+                }
             }
             Err(err) => {
                 error!("Could not render template: {:#}", err);
@@ -398,11 +802,27 @@ async fn generate_html(configuration: &Configuration, posts: &[Post], pages: &Ve
 
 async fn update(configuration: &Configuration) {
     debug!("update");
-    let mastodon_client = get_mastodon_client();
-    let telegram_client = get_telegram_client();
+    let announcers = announcer::get_announcers(configuration.get_announcers());
     let mut new_docs = Vec::new();
     let aoclient = configuration.get_archiveorg();
     let docs = aoclient.get_all_docs().await;
+    // announcements wait in the outbox, so there is none without a data store
+    let mut data = match Data::read(configuration.get_data()).await {
+        Ok(data) => Some(data),
+        Err(err) => {
+            // never overwrite a history that could not be read
+            error!("Can not read {}. {:#}", configuration.get_data(), err);
+            None
+        }
+    };
+    if let Some(data) = data.as_mut() {
+        let now = chrono::Utc::now();
+        for doc in &docs {
+            data.record(doc.get_identifier(), now, doc.get_downloads());
+        }
+    }
+    // webhook events, delivered once every episode is saved
+    let mut events = Vec::new();
     for doc in docs {
         if doc.exists().await {
             debug!("Doc {} exists", doc.get_identifier());
@@ -411,17 +831,51 @@ async fn update(configuration: &Configuration) {
             //BUG: Esto hay que revisar
             match Episode::new(&filename).await {
                 Ok(ref mut episode) => {
-                    if episode.get_downloads() != doc.get_downloads()
-                        || episode.get_version() == 0
-                        || episode.get_version() != VERSION
-                    {
+                    let mut save = false;
+                    // what listeners see changed, downloads alone don't count
+                    let mut updated = false;
+                    // transcripts and artwork can be uploaded after the episode
+                    let recent = chrono::Utc::now() - doc.get_datetime() < chrono::Duration::days(BACKFILL_DAYS);
+                    let transcript = Transcript::exists(episode::Metadata::DIR, doc.get_identifier()).await;
+                    if recent && (!transcript || episode.get_image().is_none()) {
+                        let files = ArchiveOrg::get_files(doc.get_identifier())
+                            .await
+                            .unwrap_or_default();
+                        if !transcript {
+                            updated |= get_transcript(doc.get_identifier(), &files).await;
+                        }
+                        if let (None, Some(file)) = (episode.get_image(), files::find_image(&files)) {
+                            episode.set_image(Some(ArchiveOrg::get_download_url(doc.get_identifier(), &file.name)));
+                            save = true;
+                            updated = true;
+                        }
+                    }
+                    if episode.get_version() != VERSION {
                         if episode.get_version() == 0 {
                             episode.set_datetime(doc.get_datetime());
                         }
                         episode.set_version(VERSION);
+                        save = true;
+                        updated = true;
+                    }
+                    if episode.get_downloads() != doc.get_downloads() {
                         episode.set_downloads(doc.get_downloads());
-                        match episode.save().await {
-                            Ok(_) => info!("Episode {} saved", episode.get_slug()),
+                        save = true;
+                    }
+                    if let (true, Some(data)) = (episode.is_pending_announcement(), data.as_mut()) {
+                        queue_announcements(data, episode, &announcers);
+                        episode.set_announced();
+                        save = true;
+                    }
+                    let saved = if save {
+                        match episode
+                            .save_fields(&["downloads", "version", "date", "image", "announce"])
+                            .await
+                        {
+                            Ok(_) => {
+                                info!("Episode {} saved", episode.get_slug());
+                                true
+                            }
                             Err(err) => {
                                 error!("1 Can not save episode {}. {:#}", episode.get_slug(), err);
                                 // render causes as well
@@ -430,8 +884,14 @@ async fn update(configuration: &Configuration) {
                                     error!("caused by: {:#}", next_err);
                                     err = next_err;
                                 }
+                                false
                             }
                         }
+                    } else {
+                        true
+                    };
+                    if updated && saved {
+                        add_event(&mut events, Event::EpisodeUpdated, get_episode_post(episode));
                     }
                 }
                 Err(err) => {
@@ -448,27 +908,37 @@ async fn update(configuration: &Configuration) {
             new_docs.push(doc);
         }
     }
+    // numbers go on from the last episode of the season, oldest first
+    let mut numbers = number_episodes().await;
+    new_docs.sort_by_key(|doc| doc.get_datetime());
     for doc in new_docs {
         match ArchiveOrg::get_metadata(doc.get_identifier()).await {
             Some(metadata) => {
                 match ArchiveOrg::get_mp3_metadata(doc.get_identifier()).await {
                     Some(mp3) => {
-                        let episode = Episode::combine(&doc, &metadata, &mp3);
+                        let files = ArchiveOrg::get_files(doc.get_identifier())
+                            .await
+                            .unwrap_or_default();
+                        get_transcript(doc.get_identifier(), &files).await;
+                        let mut episode = Episode::combine(&doc, &metadata, &mp3);
+                        episode.set_image(files::find_image(&files).map(|file| {
+                            ArchiveOrg::get_download_url(doc.get_identifier(), &file.name)
+                        }));
+                        let season = configuration.get_site().season;
+                        let number = numbers.entry(season).or_default();
+                        *number += 1;
+                        episode.set_episode(season, *number);
                         match episode.save().await {
                             Ok(_) => {
-                                match &telegram_client {
-                                    Some(client) => {
-                                        post_with_telegram(configuration, &episode, client).await;
-                                    }
-                                    None => {}
-                                };
-                                match &mastodon_client {
-                                    Some(client) => {
-                                        post_with_mastodon(configuration, &episode, client).await;
+                                info!("Episode {} saved", episode.get_slug());
+                                add_event(&mut events, Event::EpisodeNew, get_episode_post(&episode));
+                                if let (true, Some(data)) = (episode.is_pending_announcement(), data.as_mut()) {
+                                    queue_announcements(data, &episode, &announcers);
+                                    episode.set_announced();
+                                    if let Err(err) = episode.save_fields(&["announce"]).await {
+                                        error!("Can not save episode {}. {:#}", episode.get_slug(), err);
                                     }
-                                    None => {}
                                 }
-                                info!("Episode {} saved", episode.get_slug());
                             }
                             Err(err) => {
                                 error!("2 Can not save episode {}. {:#}", episode.get_slug(), err);
@@ -487,6 +957,83 @@ async fn update(configuration: &Configuration) {
             None => error!("Cant download from {}", doc.get_identifier()),
         }
     }
+    for (event, post) in &events {
+        webhook::notify(configuration.get_webhooks(), *event, &configuration.get_site().url, Some(post)).await;
+    }
+    if let Some(mut data) = data {
+        send_announcements(configuration, &mut data, &announcers).await;
+        match data.save(configuration.get_data()).await {
+            Ok(_) => debug!("Data saved in {}", configuration.get_data()),
+            Err(err) => error!("Can not save {}. {:#}", configuration.get_data(), err),
+        }
+    }
+}
+
+/// Keeps one event of each kind per episode, with its latest post
+fn add_event(events: &mut Vec<(Event, Post)>, event: Event, post: Post) {
+    events.retain(|(other, other_post)| *other != event || other_post.identifier != post.identifier);
+    events.push((event, post));
+}
+
+/// Saves the transcript uploaded to the archive.org item, if any, next to
+/// the episode so builds don't need to download it. Returns whether it did.
+async fn get_transcript(identifier: &str, files: &[files::ArchiveFile]) -> bool {
+    if let Some(file) = files::find_transcript(files) {
+        match ArchiveOrg::download(identifier, &file.name).await {
+            Some(content) => {
+                let output = format!(
+                    "{}/{}.{}",
+                    episode::Metadata::DIR,
+                    identifier,
+                    file.get_extension()
+                );
+                match tokio::fs::write(&output, content).await {
+                    Ok(_) => {
+                        info!("Transcript {} saved", output);
+                        return true;
+                    }
+                    Err(err) => error!("Can not save transcript {}. {:#}", output, err),
+                }
+            }
+            None => error!("Cant download {} from {}", file.name, identifier),
+        }
+    }
+    false
+}
+
+/// Post of an episode with its show notes in html, for webhooks and
+/// announcements, which are sent outside of a build
+fn get_episode_post(episode: &Episode) -> Post {
+    let mut post = episode.get_post();
+    post.content = markdown::to_html(&post.source);
+    post
+}
+
+/// Numbers the local episodes that have none yet (see `episode::number`)
+/// and returns the last number of every season. The backfill calls no
+/// webhooks, the first run would call them for the whole archive.
+async fn number_episodes() -> HashMap<Option<usize>, usize> {
+    let mut episodes = Vec::new();
+    let filenames = content::list::<episode::Metadata>().await.unwrap_or_default();
+    for filename in filenames {
+        if let Ok(episode) = Episode::new(&filename).await {
+            episodes.push(episode);
+        }
+    }
+    for index in episode::number(&mut episodes) {
+        let episode = &episodes[index];
+        if let Err(err) = episode.save_fields(&["season", "episode"]).await {
+            error!("Can not number episode {}. {:#}", episode.get_slug(), err);
+        }
+    }
+    let mut numbers = HashMap::new();
+    for episode in &episodes {
+        if let Some(number) = episode.get_episode() {
+            let last = numbers.entry(episode.get_season()).or_default();
+            *last = number.max(*last);
+        }
+    }
+    numbers
 }
 
 fn clean_path(path: &str) -> &str {
@@ -532,19 +1079,6 @@ async fn write_post(base: &str, endpoint: &str, filename: Option<&str>, content:
     }
 }
 
-async fn copy_all_files(from_dir: &str, to_dir: &str) {
-    debug!("Going to copy from {} to {}", from_dir, to_dir);
-    let mut episodes_dir = tokio::fs::read_dir(from_dir).await.unwrap();
-    while let Some(file) = episodes_dir.next_entry().await.unwrap() {
-        if file.metadata().await.unwrap().is_file() {
-            let filename = file.file_name().to_str().unwrap().to_string();
-            let input_file = format!("{}/{}", from_dir, filename);
-            let output_file = format!("{}/{}", to_dir, filename);
-            copy_file(&input_file, &output_file).await;
-        }
-    }
-}
-
 async fn create_dir(output: &str) {
     debug!("Going to create : {}", &output);
     let exists = match tokio::fs::metadata(&output).await {
@@ -590,21 +1124,6 @@ async fn create_dir(output: &str) {
     }
 }
 
-pub async fn copy_file(from: &str, to: &str) {
-    match tokio::fs::copy(from, to).await {
-        Ok(_) => debug!("Copied from {} to {}", from, to),
-        Err(err) => {
-            error!("Cant copy from {} to {}. {:#}", from, to, err);
-            let mut err = &err as &dyn std::error::Error;
-            while let Some(next_err) = err.source() {
-                error!("caused by: {:#}", next_err);
-                err = next_err;
-            }
-            std::process::exit(1);
-        }
-    }
-}
-
 pub async fn create_public(configuration: &Configuration) {
     debug!("create_public");
     let output = configuration.get_public();
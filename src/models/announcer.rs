@@ -0,0 +1,284 @@
+use async_trait::async_trait;
+use minijinja::context;
+use serde::{Serialize, Deserialize};
+use std::fmt::{self, Display};
+use tracing::{debug, warn};
+
+use super::{
+    ENV,
+    site::{Post, Site},
+};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A channel where new episodes are announced. A new channel is a module
+/// implementing it plus its line in `channels!`.
+#[async_trait]
+pub trait Announcer: Send + Sync{
+    /// Key of the channel in the outbox and in the `announcements` of
+    /// episodes, the kind of channel unless the entry has a `name`
+    fn channel(&self) -> &str;
+
+    /// Announces `post` and returns the id of the message
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>;
+}
+
+#[derive(Debug)]
+pub enum AnnounceError{
+    Render{template: String, source: minijinja::Error},
+    Request{channel: String, source: reqwest::Error},
+    // channels that don't talk http, or answer without the id of the message
+    Send{channel: String, source: BoxError},
+}
+
+impl Display for AnnounceError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            AnnounceError::Render{template, ..} => write!(f, "Can not render {}", template),
+            AnnounceError::Request{channel, ..} => write!(f, "Can not send message to {}", channel),
+            AnnounceError::Send{channel, ..} => write!(f, "Can not send message to {}", channel),
+        }
+    }
+}
+
+impl std::error::Error for AnnounceError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self{
+            AnnounceError::Render{source, ..} => Some(source),
+            AnnounceError::Request{source, ..} => Some(source),
+            AnnounceError::Send{source, ..} => Some(source.as_ref()),
+        }
+    }
+}
+
+/// Entry of the `announcers` section of the configuration. Credentials
+/// come from the environment, never from `config.yml`, so entries of the
+/// same kind post with the same account and differ only in their options,
+/// such as the template or the Telegram chat.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Channel{
+    // key in the outbox and in `announcements`, the kind when missing. Two
+    // entries of the same kind need one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub kind: Kind,
+}
+
+/// Declares the kinds of channel: the value of `channel` in the
+/// configuration, its options and its announcer, which has to provide
+/// `from_config(&options) -> Option<Self>`
+macro_rules! channels {
+    ($($key:literal => $variant:ident($config:ty, $announcer:ty)),* $(,)?) => {
+        #[derive(Debug, Serialize, Deserialize, Clone)]
+        #[serde(tag = "channel")]
+        pub enum Kind{
+            $(
+                #[serde(rename = $key)]
+                $variant($config),
+            )*
+        }
+
+        impl Kind{
+            /// The announcer of the channel, unless its credentials are missing
+            fn build(&self) -> Option<Box<dyn Announcer>>{
+                match self{
+                    $(Kind::$variant(config) => <$announcer>::from_config(config)
+                        .map(|announcer| Box::new(announcer) as Box<dyn Announcer>),)*
+                }
+            }
+        }
+
+        /// Without an `announcers` section every channel with credentials is used
+        pub fn default_channels() -> Vec<Channel>{
+            vec![$(Channel{name: None, kind: Kind::$variant(<$config>::default())},)*]
+        }
+    };
+}
+
+channels!{
+    "telegram" => Telegram(super::telegram::TelegramConfig, super::telegram::Telegram),
+    "mastodon" => Mastodon(super::mastodon::MastodonConfig, super::mastodon::Mastodon),
+    "bluesky" => Bluesky(super::bluesky::BlueskyConfig, super::bluesky::Bluesky),
+    "matrix" => Matrix(super::matrix::MatrixConfig, super::matrix::Matrix),
+    "discord" => Discord(super::discord::DiscordConfig, super::discord::Discord),
+    "slack" => Slack(super::slack::SlackConfig, super::slack::Slack),
+    "email" => Email(super::email::EmailConfig, super::email::Email),
+}
+
+/// Announcer of an entry with a `name`, which is its key
+struct Named{
+    name: String,
+    announcer: Box<dyn Announcer>,
+}
+
+#[async_trait]
+impl Announcer for Named{
+    fn channel(&self) -> &str{
+        &self.name
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        self.announcer.announce(site, post).await
+    }
+}
+
+impl Channel{
+    /// The announcer of the channel, unless its credentials are missing
+    pub fn build(&self) -> Option<Box<dyn Announcer>>{
+        let announcer = self.kind.build()?;
+        Some(match &self.name{
+            Some(name) => Box::new(Named{name: name.to_owned(), announcer}),
+            None => announcer,
+        })
+    }
+}
+
+pub fn get_announcers(channels: &[Channel]) -> Vec<Box<dyn Announcer>>{
+    unique(channels.iter().filter_map(|channel| {
+        let announcer = channel.build();
+        if announcer.is_none(){
+            debug!("Channel {:?} has no credentials", channel);
+        }
+        announcer
+    }))
+}
+
+/// Keeps the first announcer of every key
+fn unique(all: impl IntoIterator<Item = Box<dyn Announcer>>) -> Vec<Box<dyn Announcer>>{
+    let mut announcers: Vec<Box<dyn Announcer>> = Vec::new();
+    for announcer in all{
+        if announcers.iter().any(|other| other.channel() == announcer.channel()){
+            warn!("Channel {} is repeated, give each entry a different name", announcer.channel());
+        }else{
+            announcers.push(announcer);
+        }
+    }
+    announcers
+}
+
+/// Base url of the site as the templates expect it
+pub fn get_url(site: &Site) -> String{
+    if site.baseurl.is_empty(){
+        "".to_string()
+    }else if site.baseurl.starts_with('/'){
+        site.baseurl.to_owned()
+    }else{
+        format!("/{}", site.baseurl)
+    }
+}
+
+/// Absolute url of the page of a post
+pub fn get_post_url(site: &Site, post: &Post) -> String{
+    format!("{}{}/{}/", site.url.trim_end_matches('/'), get_url(site), post.slug)
+}
+
+/// Absolute url of the artwork of a post, if it has its own
+pub fn get_image_url(site: &Site, post: &Post) -> Option<String>{
+    post.image.as_ref().map(|image| {
+        if image.contains("://"){
+            image.to_owned()
+        }else{
+            format!("{}{}", site.url, image)
+        }
+    })
+}
+
+/// Absolute url of the artwork of a post or else of the site, if any
+pub fn get_artwork_url(site: &Site, post: &Post) -> Option<String>{
+    get_image_url(site, post).or_else(|| {
+        if site.avatar.is_empty(){
+            None
+        }else if site.avatar.contains("://"){
+            Some(site.avatar.to_owned())
+        }else{
+            Some(format!("{}{}", site.url, site.avatar))
+        }
+    })
+}
+
+pub fn get_audio_url(post: &Post) -> String{
+    format!("https://archive.org/download/{}/{}", post.identifier, post.filename)
+}
+
+/// Renders the message of a channel with `url`, `site`, `post`, `audio`
+/// and `image` in the context
+pub fn render(template: &str, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+    let render_error = |source| AnnounceError::Render{template: template.to_string(), source};
+    let ctx = context!{
+        url => get_url(site),
+        site => site,
+        audio => get_audio_url(post),
+        image => get_image_url(site, post),
+        post => post,
+    };
+    ENV.get_template(template)
+        .map_err(render_error)?
+        .render(ctx)
+        .map_err(render_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_artwork_url, get_post_url, render, unique, Announcer, Channel, Kind, Named};
+    use crate::models::{mastodon::Mastodon, telegram::Telegram, testing::{get_post, get_site}};
+
+    #[test]
+    fn test_render(){
+        let site = get_site();
+        let post = get_post();
+        assert_eq!(get_post_url(&site, &post), "https://atareao.es/podcast/papa-friki-1/");
+        let text = render("bluesky.html", &site, &post).unwrap();
+        assert!(text.contains("https://atareao.es/podcast/papa-friki-1/"), "{}", text);
+        assert!(render("telegram.html", &site, &post).unwrap().contains("<strong>Papá Friki 1</strong>"));
+        assert!(render("nope.html", &site, &post).is_err());
+        assert_eq!(get_artwork_url(&site, &post).unwrap(), "https://atareao.es/avatar.png");
+    }
+
+    #[test]
+    fn test_channels(){
+        let channels: Vec<Channel> = serde_yaml::from_str(r#"
+- channel: telegram
+  audio: false
+- channel: mastodon
+  name: mastodon-social
+  template: toot.html
+  instance: mastodon.social
+  visibility: unlisted
+"#).unwrap();
+        assert!(channels[0].name.is_none());
+        assert_eq!(channels[1].name.as_deref(), Some("mastodon-social"));
+        match &channels[0].kind{
+            Kind::Telegram(config) => {
+                assert!(!config.audio);
+                assert_eq!(config.template, "telegram.html");
+            },
+            _ => panic!("telegram expected"),
+        }
+        match &channels[1].kind{
+            Kind::Mastodon(config) => {
+                assert_eq!(config.template, "toot.html");
+                assert_eq!(config.instance.as_deref(), Some("mastodon.social"));
+                assert_eq!(config.visibility.as_deref(), Some("unlisted"));
+            },
+            _ => panic!("mastodon expected"),
+        }
+        assert!(serde_yaml::from_str::<Vec<Channel>>("- channel: myspace").is_err());
+    }
+
+    #[test]
+    fn test_names(){
+        let named = |name: &str, announcer: Box<dyn Announcer>| -> Box<dyn Announcer>{
+            Box::new(Named{name: name.to_string(), announcer})
+        };
+        let announcers = unique([
+            Box::new(Telegram::new("token", "@papafriki")) as Box<dyn Announcer>,
+            named("telegram-equipo", Box::new(Telegram::new("token", "@equipo"))),
+            // shares the key of the first one
+            Box::new(Telegram::new("token", "@otro")),
+            Box::new(Mastodon::new("token", "mastodon.social")),
+        ]);
+        let keys: Vec<&str> = announcers.iter().map(|announcer| announcer.channel()).collect();
+        assert_eq!(keys, vec!["telegram", "telegram-equipo", "mastodon"]);
+    }
+}
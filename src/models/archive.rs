@@ -2,6 +2,7 @@ use tracing::{info, warn, error};
 use serde::{Serialize, Deserialize, Deserializer};
 use serde_json::Value;
 use crate::models::{
+    files::ArchiveFile,
     metadata::AOMetadata,
     mp3metadata::Mp3Metadata,
     doc::Doc,
@@ -127,21 +128,52 @@ impl ArchiveOrg{
     }
 
     pub async fn get_mp3_metadata(identifier: &str) -> Option<Mp3Metadata>{
+        match Self::get_files_xml(identifier).await{
+            Some(content) => Mp3Metadata::new(&content),
+            None => None,
+        }
+    }
+
+    pub async fn get_files(identifier: &str) -> Option<Vec<ArchiveFile>>{
+        Self::get_files_xml(identifier)
+            .await
+            .map(|content| ArchiveFile::new(&content))
+    }
+
+    async fn get_files_xml(identifier: &str) -> Option<String>{
         let url = format!("{}/download/{identifier}/{identifier}_files.xml",
             BASE_URL, identifier=identifier);
         info!("url: {}", url);
+        Self::get_text(&url).await
+    }
+
+    /// Public url of a file of the item
+    pub fn get_download_url(identifier: &str, filename: &str) -> String{
+        format!("{}/download/{}/{}", BASE_URL, identifier, filename)
+    }
+
+    /// Content of a file of the item
+    pub async fn download(identifier: &str, filename: &str) -> Option<String>{
+        let url = Self::get_download_url(identifier, filename);
+        info!("url: {}", url);
+        Self::get_text(&url).await
+    }
+
+    async fn get_text(url: &str) -> Option<String>{
         let client = reqwest::Client::new();
-        let response = client
+        let response = match client
             .get(url)
             .send()
-            .await
-            .unwrap();
+            .await{
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Error: {}", e);
+                    return None;
+                },
+            };
         match response.status() {
             reqwest::StatusCode::OK => {
-                match response.text().await{
-                    Ok(content) => Mp3Metadata::new(&content),
-                    Err(_) => None,
-                }
+                response.text().await.ok()
             }
             _ => {
                 None
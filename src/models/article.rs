@@ -1,122 +1,27 @@
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info, error};
-use gray_matter::{Matter, engine::YAML};
-use comrak::{markdown_to_html, ComrakOptions};
 
 use super::{
-    Post,
     Layout,
-    utils::{
-        get_slug,
-        get_unix_time,
-        get_excerpt,
-    },
+    content::{Common, Schema},
 };
 
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Metadata{
-    pub title: String,
-    pub date: String,
-    pub excerpt: String,
-    pub slug: String,
-    pub version: usize,
+pub struct Metadata{
+    #[serde(flatten)]
+    pub common: Common,
 }
 
-impl Metadata{
-    pub fn get_filename(&self) -> String {
-        format!("posts/{}.md", self.slug)
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Article{
-    metadata: Metadata,
-    pub content: String,
-}
-
-
-impl Article{
-    pub fn get_post(&self) -> Post{
-        let date = get_unix_time(&self.metadata.date);
-        let content = markdown_to_html(&self.content, &ComrakOptions::default());
-        Post{
-            title: self.metadata.title.clone(),
-            date,
-            version: self.metadata.version,
-            excerpt: self.metadata.excerpt.clone(),
-            layout: Layout::POST,
-            slug: self.metadata.slug.clone(),
-            subject: Vec::new(),
-            content,
-            identifier: self.metadata.slug.clone(),
-            filename: "".to_string(),
-            length: 0,
-            size: 0,
-            number: 0,
-            downloads: 0,
-        }
-    }
-
-    pub async fn new(filename: &str) -> Result<Self, serde_json::Error>{
-        let mut save = false;
-        let filename = format!("posts/{}", filename);
-        debug!("Filename: {}", filename);
-        let data = tokio::fs::read_to_string(&filename)
-            .await
-            .unwrap();
-        let matter = Matter::<YAML>::new();
-        let result = matter.parse(&data);
-        let mut metadata: Metadata = result.data.unwrap().deserialize()?;
-        debug!("Metadata: {:?}", &metadata);
-        if metadata.slug.is_empty(){
-            debug!("Is empty");
-            metadata.slug = get_slug(&metadata.title);
-            save = true;
-        }
-        if metadata.excerpt.is_empty(){
-            metadata.excerpt = match result.excerpt {
-                Some(excerpt) => {
-                    save = true;
-                    excerpt
-                },
-                None => get_excerpt(&result.content).to_string(),
-            };
-        }
-        debug!("Metadata: {:?}", &metadata);
-        let article = Article{
-            metadata,
-            content: result.content,
-        };
-        if save{
-            match article.save().await{
-                Ok(_) => {
-                    info!("Saved article {}", article.get_filename());
-                    if filename != article.get_filename(){
-                        match tokio::fs::remove_file(&filename).await{
-                            Ok(_) => info!("Removed {}", &filename),
-                            Err(e) => error!("Cant remove {}. {}", &filename, e),
-                        }
-                    }
-                },
-                Err(_) => error!("Cant save article {}", article.get_filename()),
-            }
-        }
-        Ok(article)
-    }
+impl Schema for Metadata{
+    const DIR: &'static str = "posts";
+    const LAYOUT: Layout = Layout::POST;
 
-    pub fn get_filename(&self) -> String{
-        self.metadata.get_filename()
+    fn common(&self) -> &Common{
+        &self.common
     }
 
-    pub async fn save(&self)-> tokio::io::Result<()>{
-        let mut content = String::new();
-        content.push_str("---\n");
-        content.push_str(&serde_yaml::to_string(&self.metadata).unwrap());
-        content.push_str("---\n");
-        content.push_str(&self.content);
-        debug!("Content: {}", content);
-        tokio::fs::write(self.get_filename(), content).await
+    fn common_mut(&mut self) -> &mut Common{
+        &mut self.common
     }
 }
 
@@ -129,19 +34,19 @@ mod tests {
     };
     use std::str::FromStr;
     use tracing::debug;
-    use crate::models::article::Article;
+    use crate::models::{content::Document, article::Metadata};
 
     #[tokio::test]
     async fn test_article(){
-        tracing_subscriber::registry()
+        let _ = tracing_subscriber::registry()
             .with(EnvFilter::from_str("debug").unwrap())
             .with(tracing_subscriber::fmt::layer())
-            .init();
+            .try_init();
 
-        let article = Article::new("pihole.md").await.unwrap();
+        let article = Document::<Metadata>::new("instalacion-docker-y-pihole.md").await.unwrap();
         debug!("=========================");
         debug!("{:?}", article);
         debug!("=========================");
-        assert!(!article.metadata.title.is_empty());
+        assert!(!article.metadata.common.title.is_empty());
     }
 }
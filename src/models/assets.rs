@@ -0,0 +1,231 @@
+use serde::{Serialize, Deserialize};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+};
+use tracing::{debug, warn};
+
+const HASH_LENGTH: usize = 8;
+
+/// Maps every published asset (relative to the assets directory, with `/`
+/// separators) to the name it was written with. Filled by `Assets::build`
+/// and read by the `asset_url` template function.
+pub static MANIFEST: Lazy<RwLock<HashMap<String, String>>> = Lazy::new(|| {
+    RwLock::new(HashMap::new())
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Assets{
+    #[serde(default = "default_source")]
+    pub source: String,
+    #[serde(default)]
+    pub minify: bool,
+    #[serde(default)]
+    pub hash: bool,
+}
+
+fn default_source() -> String{
+    "assets".to_string()
+}
+
+impl Default for Assets{
+    fn default() -> Self{
+        Self{
+            source: default_source(),
+            minify: false,
+            hash: false,
+        }
+    }
+}
+
+impl Assets{
+    /// Copies every file under `source` (recursively) into `output`,
+    /// minifying CSS and JS and adding content hashes to the file names when
+    /// enabled. `style_css` is published too when it lives outside `source`.
+    pub async fn build(&self, style_css: &str, output: &str) -> tokio::io::Result<HashMap<String, String>>{
+        let source = Path::new(&self.source);
+        let mut files = Vec::new();
+        if tokio::fs::metadata(source).await.map(|m| m.is_dir()).unwrap_or(false){
+            files = list_files(source).await?;
+        }else{
+            warn!("Assets directory {} not found", &self.source);
+        }
+        let style = Path::new(style_css);
+        if !style_css.is_empty() && !style.starts_with(source){
+            match style.file_name(){
+                Some(name) if tokio::fs::metadata(style).await.is_ok() => {
+                    files.push((name.to_string_lossy().to_string(), style.to_path_buf()));
+                },
+                _ => warn!("Style {} not found", style_css),
+            }
+        }
+        // CSS goes last so it can point to the final names of fonts and images
+        files.sort_by_key(|(name, _)| name.ends_with(".css"));
+        tokio::fs::create_dir_all(output).await?;
+        let mut manifest = HashMap::new();
+        for (name, path) in files{
+            let mut content = tokio::fs::read(&path).await?;
+            if name.ends_with(".css"){
+                if let Ok(text) = String::from_utf8(content.clone()){
+                    let text = rewrite_css_urls(&name, &text, &manifest);
+                    content = if self.minify && !is_minified(&name){
+                        minify_css(&text).into_bytes()
+                    }else{
+                        text.into_bytes()
+                    };
+                }
+            }else if name.ends_with(".js") && self.minify && !is_minified(&name){
+                if let Ok(text) = std::str::from_utf8(&content){
+                    content = minifier::js::minify(text).to_string().into_bytes();
+                }
+            }
+            let published = if self.hash{
+                hashed_name(&name, &content_hash(&content))
+            }else{
+                name.clone()
+            };
+            let target = Path::new(output).join(&published);
+            if let Some(parent) = target.parent(){
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&target, &content).await?;
+            debug!("Asset {} published as {}", &name, &published);
+            manifest.insert(name, published);
+        }
+        *MANIFEST.write().unwrap() = manifest.clone();
+        Ok(manifest)
+    }
+}
+
+async fn list_files(root: &Path) -> tokio::io::Result<Vec<(String, PathBuf)>>{
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop(){
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await?{
+            let path = entry.path();
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir(){
+                pending.push(path);
+            }else if metadata.is_file(){
+                let name = path.strip_prefix(root)
+                    .unwrap()
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<String>>()
+                    .join("/");
+                files.push((name, path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_minified(name: &str) -> bool{
+    name.ends_with(".min.css") || name.ends_with(".min.js")
+}
+
+fn minify_css(text: &str) -> String{
+    match minifier::css::minify(text){
+        Ok(minified) => minified.to_string(),
+        Err(e) => {
+            warn!("Can not minify css: {}", e);
+            text.to_string()
+        },
+    }
+}
+
+pub fn content_hash(content: &[u8]) -> String{
+    let hash = format!("{:x}", Sha256::digest(content));
+    hash[..HASH_LENGTH].to_string()
+}
+
+/// `fonts/icons.woff2` -> `fonts/icons.<hash>.woff2`
+pub fn hashed_name(name: &str, hash: &str) -> String{
+    let (dir, file) = match name.rfind('/'){
+        Some(pos) => (&name[..=pos], &name[pos + 1..]),
+        None => ("", name),
+    };
+    match file.rfind('.'){
+        Some(pos) if pos > 0 => format!("{}{}.{}{}", dir, &file[..pos], hash, &file[pos..]),
+        _ => format!("{}{}.{}", dir, file, hash),
+    }
+}
+
+/// Points the relative `url(...)` references of a stylesheet to the names
+/// the referenced assets were published with.
+fn rewrite_css_urls(name: &str, css: &str, manifest: &HashMap<String, String>) -> String{
+    let re = Regex::new(r#"url\(\s*(['"]?)([^'")]+)(['"]?)\s*\)"#).unwrap();
+    let base = match name.rfind('/'){
+        Some(pos) => &name[..pos],
+        None => "",
+    };
+    re.replace_all(css, |caps: &regex::Captures| {
+        let reference = &caps[2];
+        if reference.starts_with("data:") || reference.starts_with('#')
+                || reference.starts_with('/') || reference.contains("://"){
+            return caps[0].to_string();
+        }
+        let split = reference.find(['?', '#']).unwrap_or(reference.len());
+        let (path, suffix) = reference.split_at(split);
+        let key = normalize(&format!("{}/{}", base, path));
+        match manifest.get(&key){
+            Some(published) => {
+                let file = published.rsplit('/').next().unwrap();
+                let path = match path.rfind('/'){
+                    Some(pos) => format!("{}{}", &path[..=pos], file),
+                    None => file.to_string(),
+                };
+                format!("url({}{}{}{})", &caps[1], path, suffix, &caps[3])
+            },
+            None => caps[0].to_string(),
+        }
+    }).to_string()
+}
+
+fn normalize(path: &str) -> String{
+    let mut parts: Vec<String> = Vec::new();
+    for component in Path::new(path).components(){
+        match component{
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop();
+            },
+            _ => {},
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::{hashed_name, rewrite_css_urls, content_hash};
+
+    #[test]
+    fn test_hashed_name(){
+        assert_eq!(hashed_name("style.css", "abcd1234"), "style.abcd1234.css");
+        assert_eq!(hashed_name("fonts/icons.woff2", "abcd1234"), "fonts/icons.abcd1234.woff2");
+        assert_eq!(hashed_name("LICENSE", "abcd1234"), "LICENSE.abcd1234");
+        assert_eq!(content_hash(b"aopodcast").len(), 8);
+    }
+
+    #[test]
+    fn test_rewrite_css_urls(){
+        let mut manifest = HashMap::new();
+        manifest.insert("fonts/icons.woff2".to_string(), "fonts/icons.abcd1234.woff2".to_string());
+        manifest.insert("images/logo.png".to_string(), "images/logo.1234abcd.png".to_string());
+        let css = r#"@font-face{src:url("../fonts/icons.woff2?v=1") format("woff2")}
+.logo{background:url(../images/logo.png)}
+.remote{background:url(https://example.com/a.png)}"#;
+        let rewritten = rewrite_css_urls("css/site.css", css, &manifest);
+        assert!(rewritten.contains(r#"url("../fonts/icons.abcd1234.woff2?v=1")"#));
+        assert!(rewritten.contains("url(../images/logo.1234abcd.png)"));
+        assert!(rewritten.contains("url(https://example.com/a.png)"));
+    }
+}
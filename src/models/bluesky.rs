@@ -0,0 +1,291 @@
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tracing::{info, error};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{
+    announcer::{Announcer, AnnounceError, BoxError, render, get_artwork_url, get_post_url},
+    site::{Post, Site},
+};
+
+/// Longest post Bluesky accepts, in graphemes
+const MAX_GRAPHEMES: usize = 300;
+const ELLIPSIS: &str = "…";
+
+static LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://[^\s<>]+[^\s<>.,;:!?)'\x22]").unwrap());
+
+/// Options of the Bluesky channel. The app password comes from
+/// `BLUESKY_PASSWORD`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BlueskyConfig{
+    pub template: String,
+    // `BLUESKY_HANDLE` when missing
+    pub handle: Option<String>,
+    // PDS of the account
+    pub service: String,
+}
+
+impl Default for BlueskyConfig{
+    fn default() -> Self{
+        Self{
+            template: "bluesky.html".to_string(),
+            handle: None,
+            service: "https://bsky.social".to_string(),
+        }
+    }
+}
+
+pub struct Bluesky{
+    service: String,
+    handle: String,
+    password: String,
+    template: String,
+}
+
+/// Tokens of a logged in account
+struct Session{
+    did: String,
+    access_jwt: String,
+}
+
+impl Bluesky{
+    pub fn new(service: &str, handle: &str, password: &str) -> Self{
+        Self{
+            service: service.trim_end_matches('/').to_string(),
+            handle: handle.to_string(),
+            password: password.to_string(),
+            template: BlueskyConfig::default().template,
+        }
+    }
+
+    pub fn from_config(config: &BlueskyConfig) -> Option<Self>{
+        let password = std::env::var("BLUESKY_PASSWORD").ok()?;
+        let handle = match &config.handle{
+            Some(handle) => handle.to_owned(),
+            None => std::env::var("BLUESKY_HANDLE").ok()?,
+        };
+        Some(Self{
+            template: config.template.clone(),
+            ..Self::new(&config.service, &handle, &password)
+        })
+    }
+
+    async fn login(&self, client: &Client) -> Result<Session, BoxError>{
+        let url = format!("{}/xrpc/com.atproto.server.createSession", self.service);
+        let response = client.post(&url)
+            .json(&json!({"identifier": self.handle, "password": self.password}))
+            .send()
+            .await?;
+        let status = response.status();
+        let session: Value = response.json().await.unwrap_or_default();
+        if !status.is_success(){
+            let message = session["message"].as_str().unwrap_or_default();
+            return Err(format!("Can not log in to {} as {}. {} {}", self.service, self.handle, status, message).into());
+        }
+        let field = |name: &str| session[name].as_str()
+            .filter(|value| !value.is_empty())
+            .map(|value| value.to_string())
+            .ok_or_else(|| format!("{} answered without {}", url, name));
+        Ok(Session{
+            did: field("did")?,
+            access_jwt: field("accessJwt")?,
+        })
+    }
+
+    /// Uploads the picture at `image` and returns its blob
+    async fn upload(&self, client: &Client, session: &Session, image: &str) -> Result<Value, reqwest::Error>{
+        let response = client.get(image)
+            .send()
+            .await?
+            .error_for_status()?;
+        let mime = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|mime| mime.to_str().ok())
+            .unwrap_or("image/jpeg")
+            .to_string();
+        let bytes = response.bytes().await?;
+        let url = format!("{}/xrpc/com.atproto.repo.uploadBlob", self.service);
+        let answer: Value = client.post(&url)
+            .bearer_auth(&session.access_jwt)
+            .header(reqwest::header::CONTENT_TYPE, mime)
+            .body(bytes)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(answer["blob"].clone())
+    }
+
+    /// Creates a post with `text` and a link card, returns its `at://` uri
+    pub async fn post(&self, text: &str, card: Card<'_>) -> Result<String, BoxError>{
+        let client = Client::new();
+        let session = self.login(&client).await?;
+        let text = truncate(text.trim(), MAX_GRAPHEMES);
+        let mut external = json!({
+            "uri": card.uri,
+            "title": card.title,
+            "description": card.description,
+        });
+        if let Some(image) = card.image{
+            match self.upload(&client, &session, image).await{
+                Ok(blob) => external["thumb"] = blob,
+                Err(err) => error!("Can not upload {} to Bluesky. {:#}", image, err),
+            }
+        }
+        let mut record = json!({
+            "$type": "app.bsky.feed.post",
+            "text": text,
+            "facets": facets(&text),
+            "createdAt": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            "embed": {
+                "$type": "app.bsky.embed.external",
+                "external": external,
+            },
+        });
+        if let Some(language) = card.language{
+            record["langs"] = json!([language]);
+        }
+        let url = format!("{}/xrpc/com.atproto.repo.createRecord", self.service);
+        let answer: Value = client.post(&url)
+            .bearer_auth(&session.access_jwt)
+            .json(&json!({
+                "repo": session.did,
+                "collection": "app.bsky.feed.post",
+                "record": record,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        answer["uri"].as_str()
+            .map(|id| id.to_string())
+            .ok_or_else(|| format!("{} answered without the id of the post", url).into())
+    }
+}
+
+/// Link card shown under the post
+pub struct Card<'a>{
+    pub uri: &'a str,
+    pub title: &'a str,
+    pub description: &'a str,
+    pub image: Option<&'a str>,
+    pub language: Option<&'a str>,
+}
+
+/// Cuts `text` to `limit` graphemes, ellipsis included, never in the
+/// middle of a link
+fn truncate(text: &str, limit: usize) -> String{
+    if text.graphemes(true).count() <= limit{
+        return text.to_string();
+    }
+    let mut end = text.grapheme_indices(true)
+        .nth(limit - 1)
+        .map(|(index, _)| index)
+        .unwrap_or(text.len());
+    if let Some(link) = LINK.find_iter(text).find(|link| link.start() < end && end < link.end()){
+        end = link.start();
+    }
+    format!("{}{}", text[..end].trim_end(), ELLIPSIS)
+}
+
+/// Link facets of `text`, Bluesky does not detect them by itself. Offsets
+/// are in bytes of the UTF-8 text.
+fn facets(text: &str) -> Vec<Value>{
+    LINK.find_iter(text)
+        .map(|link| json!({
+            "index": {"byteStart": link.start(), "byteEnd": link.end()},
+            "features": [{
+                "$type": "app.bsky.richtext.facet#link",
+                "uri": link.as_str(),
+            }],
+        }))
+        .collect()
+}
+
+#[async_trait]
+impl Announcer for Bluesky{
+    fn channel(&self) -> &str{
+        "bluesky"
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        let text = render(&self.template, site, post)?;
+        info!("Bluesky: {text}");
+        let uri = get_post_url(site, post);
+        let image = get_artwork_url(site, post);
+        let card = Card{
+            uri: &uri,
+            title: &post.title,
+            description: &post.excerpt,
+            image: image.as_deref(),
+            language: Some(site.language.as_str()).filter(|language| !language.is_empty()),
+        };
+        self.post(&text, card)
+            .await
+            .map_err(|source| AnnounceError::Send{channel: self.channel().to_string(), source})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{facets, truncate, Bluesky, Card, ELLIPSIS};
+    use unicode_segmentation::UnicodeSegmentation;
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::{method, path}};
+
+    #[test]
+    fn test_truncate(){
+        assert_eq!(truncate("Hola", 300), "Hola");
+        let text = "👨‍👩‍👧".repeat(10);
+        assert_eq!(truncate(&text, 10), text);
+        let text = "ñ".repeat(20);
+        let truncated = truncate(&text, 10);
+        assert_eq!(truncated.graphemes(true).count(), 10);
+        assert!(truncated.ends_with(ELLIPSIS));
+        let text = format!("{} https://example.com/episodio/largo", "a".repeat(10));
+        assert_eq!(truncate(&text, 20), format!("{}{}", "a".repeat(10), ELLIPSIS));
+    }
+
+    #[test]
+    fn test_facets(){
+        let text = "Nuevo episodio 🎧 https://atareao.es/podcast/pf-1/. ¡Escúchalo!";
+        let facets = facets(text);
+        assert_eq!(facets.len(), 1);
+        let start = facets[0]["index"]["byteStart"].as_u64().unwrap() as usize;
+        let end = facets[0]["index"]["byteEnd"].as_u64().unwrap() as usize;
+        assert_eq!(&text[start..end], "https://atareao.es/podcast/pf-1/");
+        assert_eq!(facets[0]["features"][0]["uri"], "https://atareao.es/podcast/pf-1/");
+        assert!(super::facets("Sin enlaces").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_login_errors(){
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "error": "AuthenticationRequired",
+                "message": "Invalid identifier or password",
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"did": "did:plc:papafriki"})))
+            .mount(&server)
+            .await;
+        let bluesky = Bluesky::new(&server.uri(), "papafriki.bsky.social", "secreto");
+        let card = || Card{uri: "https://atareao.es/podcast/pf-1/", title: "Papá Friki 1", description: "", image: None, language: None};
+        let error = bluesky.post("Hola", card()).await.unwrap_err().to_string();
+        assert!(error.contains("Invalid identifier or password"), "{}", error);
+        let error = bluesky.post("Hola", card()).await.unwrap_err().to_string();
+        assert!(error.ends_with("without accessJwt"), "{}", error);
+    }
+}
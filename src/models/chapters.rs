@@ -0,0 +1,166 @@
+use serde::{Serialize, Deserialize, Deserializer, de};
+use serde_json::{json, Value};
+use regex::Regex;
+use std::fmt;
+
+const CHAPTERS_VERSION: &str = "1.2.0";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Chapter{
+    #[serde(deserialize_with = "deserialize_start")]
+    pub start: f64,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub img: Option<String>,
+    // `hh:mm:ss` label for templates
+    #[serde(skip_deserializing, skip_serializing_if = "String::is_empty")]
+    pub time: String,
+}
+
+impl Chapter{
+    pub fn new(start: f64, title: &str) -> Self{
+        Self{
+            start,
+            title: title.to_string(),
+            url: None,
+            img: None,
+            time: format_timestamp(start),
+        }
+    }
+}
+
+fn deserialize_start<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where D: Deserializer<'de>{
+    struct Start;
+
+    impl<'de> de::Visitor<'de> for Start {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("seconds or a hh:mm:ss timestamp")
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where E: de::Error
+        {
+            Ok(value as f64)
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+            where E: de::Error
+        {
+            Ok(value as f64)
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+            where E: de::Error
+        {
+            Ok(value)
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where E: de::Error
+        {
+            parse_timestamp(value)
+                .ok_or_else(|| E::custom(format!("invalid timestamp `{}`", value)))
+        }
+    }
+
+    deserializer.deserialize_any(Start)
+}
+
+/// `12:30`, `00:12:30` or `00:12:30.5` to seconds
+pub fn parse_timestamp(value: &str) -> Option<f64>{
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() < 2 || parts.len() > 3{
+        return None;
+    }
+    let mut seconds = 0.0;
+    for part in parts{
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+pub fn format_timestamp(seconds: f64) -> String{
+    let seconds = seconds as u64;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Chapters from the show notes: every line starting with a timestamp, as in
+/// `00:12:30 Pi-hole setup` or `- [12:30] Pi-hole setup`. At least two lines
+/// are needed so a lone time in the text is not taken as a chapter.
+pub fn parse_chapters(markdown: &str) -> Vec<Chapter>{
+    let re = Regex::new(
+        r"^\s*(?:[-*+]\s+)?[\[(]?((?:\d{1,2}:)?\d{1,2}:\d{2})[\])]?\s*[-–—:]?\s+(\S.*?)\s*$"
+    ).unwrap();
+    let chapters: Vec<Chapter> = markdown.lines()
+        .filter_map(|line| re.captures(line))
+        .filter_map(|caps| {
+            parse_timestamp(&caps[1]).map(|start| Chapter::new(start, &caps[2]))
+        })
+        .collect();
+    if chapters.len() < 2{
+        return Vec::new();
+    }
+    chapters
+}
+
+/// Podcasting 2.0 JSON chapters document
+pub fn to_json(chapters: &[Chapter]) -> Value{
+    let chapters: Vec<Value> = chapters.iter()
+        .map(|chapter| {
+            let mut value = json!({
+                "startTime": chapter.start,
+                "title": chapter.title,
+            });
+            if let Some(url) = &chapter.url{
+                value["url"] = json!(url);
+            }
+            if let Some(img) = &chapter.img{
+                value["img"] = json!(img);
+            }
+            value
+        })
+        .collect();
+    json!({
+        "version": CHAPTERS_VERSION,
+        "chapters": chapters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_chapters, parse_timestamp, to_json, Chapter};
+
+    #[test]
+    fn test_parse_timestamp(){
+        assert_eq!(parse_timestamp("12:30"), Some(750.0));
+        assert_eq!(parse_timestamp("01:02:15"), Some(3735.0));
+        assert_eq!(parse_timestamp("1:02:15.5"), Some(3735.5));
+        assert_eq!(parse_timestamp("1215"), None);
+    }
+
+    #[test]
+    fn test_parse_chapters(){
+        let notes = "Hoy hablamos de Pi-hole.\n\n00:00 Introducción\n- 00:12:30 Pi-hole setup\n* [45:10] - Despedida\n\nNos vemos a las 10:30 de la mañana";
+        let chapters = parse_chapters(notes);
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[1], Chapter::new(750.0, "Pi-hole setup"));
+        assert_eq!(chapters[2].title, "Despedida");
+        assert_eq!(chapters[2].time, "00:45:10");
+        assert!(parse_chapters("Empezamos a las 10:30 en punto").is_empty());
+    }
+
+    #[test]
+    fn test_front_matter_chapters(){
+        let chapters: Vec<Chapter> = serde_yaml::from_str("- start: 00:12:30\n  title: Pi-hole\n- start: 800\n  title: Docker\n  url: https://docker.com").unwrap();
+        assert_eq!(chapters[0].start, 750.0);
+        assert_eq!(chapters[1].start, 800.0);
+        let json = to_json(&chapters);
+        assert_eq!(json["version"], "1.2.0");
+        assert_eq!(json["chapters"][1]["url"], "https://docker.com");
+    }
+}
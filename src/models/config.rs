@@ -2,7 +2,8 @@ use serde::{Serialize, Deserialize};
 use tokio::fs::read_to_string;
 use std::{process, fmt::{self, Display}};
 
-use super::{site::Site, archive::ArchiveOrg};
+use super::{site::Site, archive::ArchiveOrg, assets::Assets, theme::DEFAULT_THEME, markdown::Markdown, search::Search,
+    announcer::{Channel, default_channels}, webhook::Webhook, person::PersonConfig};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Configuration{
@@ -12,6 +13,25 @@ pub struct Configuration{
     style_css: String,
     archiveorg: ArchiveOrg,
     site: Site,
+    #[serde(default)]
+    assets: Assets,
+    #[serde(default = "default_theme")]
+    theme: String,
+    #[serde(default)]
+    markdown: Markdown,
+    #[serde(default)]
+    search: Search,
+    #[serde(default = "default_channels")]
+    announcers: Vec<Channel>,
+    #[serde(default)]
+    webhooks: Vec<Webhook>,
+    // people without a file in `people/`
+    #[serde(default)]
+    people: Vec<PersonConfig>,
+}
+
+fn default_theme() -> String{
+    DEFAULT_THEME.to_string()
 }
 
 impl Display for Configuration{
@@ -49,6 +69,34 @@ impl Configuration {
         &self.style_css
     }
 
+    pub fn get_assets(&self) -> &Assets{
+        &self.assets
+    }
+
+    pub fn get_theme(&self) -> &str{
+        &self.theme
+    }
+
+    pub fn get_markdown(&self) -> &Markdown{
+        &self.markdown
+    }
+
+    pub fn get_search(&self) -> &Search{
+        &self.search
+    }
+
+    pub fn get_announcers(&self) -> &[Channel]{
+        &self.announcers
+    }
+
+    pub fn get_webhooks(&self) -> &[Webhook]{
+        &self.webhooks
+    }
+
+    pub fn get_people(&self) -> &[PersonConfig]{
+        &self.people
+    }
+
     pub async fn read_configuration() -> Configuration{
         let content = match read_to_string("config.yml")
             .await {
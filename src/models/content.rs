@@ -0,0 +1,436 @@
+use chrono::{DateTime, Timelike, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+use tracing::{debug, info};
+use gray_matter::{Matter, engine::YAML};
+use std::{fmt::{self, Debug, Display}, path::Path};
+
+use super::{
+    frontmatter::FrontMatter,
+    Post,
+    Layout,
+    utils::{
+        get_slug,
+        get_excerpt,
+        is_published,
+        deserialize_option_datetime,
+        string_or_seq_string,
+    },
+};
+
+/// Front matter fields every kind of content shares.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Common{
+    pub title: String,
+    #[serde(default, alias = "datetime", deserialize_with = "deserialize_option_datetime", serialize_with = "serialize_date")]
+    pub date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub excerpt: String,
+    #[serde(default)]
+    pub slug: String,
+    #[serde(default)]
+    pub version: usize,
+    #[serde(default, alias = "tags", deserialize_with = "string_or_seq_string", skip_serializing_if = "Vec::is_empty")]
+    pub subject: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub draft: bool,
+    #[serde(default, deserialize_with = "deserialize_option_datetime", skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+/// Dates at midnight are written back as plain `YYYY-MM-DD`
+fn serialize_date<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+where S: Serializer{
+    match date{
+        Some(date) if date.num_seconds_from_midnight() == 0 && date.nanosecond() == 0 =>
+            serializer.serialize_str(&date.format("%Y-%m-%d").to_string()),
+        Some(date) => serializer.serialize_str(&date.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The front matter of a kind of content. Implementing it is all a new
+/// content type needs to be read, normalised and turned into a `Post`.
+pub trait Schema: Serialize + DeserializeOwned + Debug{
+    /// Directory holding the Markdown sources
+    const DIR: &'static str;
+    const LAYOUT: Layout;
+
+    /// Other names of front matter keys, as `(alias, key)`
+    const ALIASES: &'static [(&'static str, &'static str)] = &[];
+
+    fn common(&self) -> &Common;
+
+    fn common_mut(&mut self) -> &mut Common;
+
+    fn get_filename(&self) -> String{
+        format!("{}/{}.md", Self::DIR, self.common().slug)
+    }
+
+    /// Slug of a source at `path` without one
+    fn default_slug(&self, _path: &str) -> String{
+        get_slug(&self.common().title)
+    }
+
+    /// Adds the fields specific to this content type. `content` is the
+    /// Markdown source.
+    fn fill_post(&self, _content: &str, _post: &mut Post){}
+}
+
+#[derive(Debug)]
+pub enum ContentError{
+    Io{path: String, source: std::io::Error},
+    FrontMatter{path: String},
+    Date{path: String},
+    Metadata{path: String, source: serde_json::Error},
+    Serialize{path: String, source: serde_yaml::Error},
+}
+
+impl Display for ContentError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            ContentError::Io{path, ..} => write!(f, "Can not access {}", path),
+            ContentError::FrontMatter{path} => write!(f, "No front matter in {}", path),
+            ContentError::Date{path} => write!(f, "No date nor publish_at in {}", path),
+            ContentError::Metadata{path, ..} => write!(f, "Invalid front matter in {}", path),
+            ContentError::Serialize{path, ..} => write!(f, "Can not serialize {}", path),
+        }
+    }
+}
+
+impl std::error::Error for ContentError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self{
+            ContentError::Io{source, ..} => Some(source),
+            ContentError::FrontMatter{..} => None,
+            ContentError::Date{..} => None,
+            ContentError::Metadata{source, ..} => Some(source),
+            ContentError::Serialize{source, ..} => Some(source),
+        }
+    }
+}
+
+/// Lists the Markdown files of the directory of a content type
+pub async fn list<S: Schema>() -> Result<Vec<String>, ContentError>{
+    let io_error = |source| ContentError::Io{path: S::DIR.to_string(), source};
+    let mut filenames = Vec::new();
+    let mut dir = tokio::fs::read_dir(S::DIR).await.map_err(io_error)?;
+    while let Some(file) = dir.next_entry().await.map_err(io_error)?{
+        if file.metadata().await.map_err(io_error)?.is_file(){
+            let filename = file.file_name().to_string_lossy().to_string();
+            if filename.ends_with(".md"){
+                filenames.push(filename);
+            }
+        }
+    }
+    filenames.sort();
+    Ok(filenames)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Document<S>{
+    pub metadata: S,
+    pub content: String,
+}
+
+impl<S: Schema> Document<S>{
+    /// Reads `filename` from the directory of the content type. Reading never
+    /// writes: missing slugs and excerpts are only filled in memory (see `fix`).
+    pub async fn new(filename: &str) -> Result<Self, ContentError>{
+        let filename = format!("{}/{}", S::DIR, filename);
+        debug!("Filename: {}", filename);
+        let data = tokio::fs::read_to_string(&filename)
+            .await
+            .map_err(|source| ContentError::Io{path: filename.clone(), source})?;
+        let (document, _) = Self::parse(&filename, &data)?;
+        Ok(document)
+    }
+
+    /// Fails when the source has neither `date` nor `publish_at`, as it
+    /// could not be placed in the site. Old episodes get theirs on `update`.
+    pub fn check_date(self, path: &str) -> Result<Self, ContentError>{
+        let common = self.metadata.common();
+        if common.date.is_none() && common.publish_at.is_none(){
+            return Err(ContentError::Date{path: path.to_string()});
+        }
+        Ok(self)
+    }
+
+    /// Parses a Markdown source filling in the slug and the excerpt. Also
+    /// returns whether the front matter changed.
+    pub fn parse(path: &str, data: &str) -> Result<(Self, bool), ContentError>{
+        let mut save = false;
+        let matter = Matter::<YAML>::new();
+        let result = matter.parse(data);
+        let metadata_error = |source| ContentError::Metadata{path: path.to_string(), source};
+        let mut value: serde_json::Value = result.data
+            .ok_or_else(|| ContentError::FrontMatter{path: path.to_string()})?
+            .deserialize()
+            .map_err(metadata_error)?;
+        if let Some(fields) = value.as_object_mut(){
+            for (alias, key) in S::ALIASES{
+                if !fields.contains_key(*key){
+                    if let Some(field) = fields.remove(*alias){
+                        fields.insert(key.to_string(), field);
+                    }
+                }
+            }
+        }
+        let mut metadata: S = serde_json::from_value(value).map_err(metadata_error)?;
+        debug!("Metadata: {:?}", &metadata);
+        if metadata.common().slug.is_empty(){
+            debug!("Is empty");
+            metadata.common_mut().slug = metadata.default_slug(path);
+            save = true;
+        }
+        let common = metadata.common_mut();
+        if common.excerpt.is_empty(){
+            common.excerpt = match result.excerpt {
+                Some(excerpt) => {
+                    save = true;
+                    excerpt
+                },
+                None => get_excerpt(&result.content).to_string(),
+            };
+        }
+        Ok((Self{
+            metadata,
+            content: result.content,
+        }, save))
+    }
+
+    pub fn get_post(&self) -> Post{
+        let common = self.metadata.common();
+        let mut post = Post{
+            layout: S::LAYOUT,
+            slug: common.slug.clone(),
+            excerpt: common.excerpt.clone(),
+            title: common.title.clone(),
+            content: String::new(),
+            subject: common.subject.clone(),
+            date: common.date.or(common.publish_at).unwrap_or_default(),
+            version: common.version,
+            identifier: common.slug.clone(),
+            filename: "".to_string(),
+            size: 0,
+            length: 0,
+            number: 0,
+            downloads: 0,
+            draft: common.draft,
+            publish_at: common.publish_at,
+            season: None,
+            episode: None,
+            episode_type: None,
+            chapters: Vec::new(),
+            transcript: None,
+            people: Vec::new(),
+            image: None,
+            toc: Vec::new(),
+            related: Vec::new(),
+            previous: None,
+            next: None,
+            source: self.content.clone(),
+        };
+        self.metadata.fill_post(&self.content, &mut post);
+        post
+    }
+
+    pub fn is_published(&self) -> bool{
+        let common = self.metadata.common();
+        is_published(common.draft, common.publish_at)
+    }
+
+    #[allow(dead_code)]
+    pub fn get_title(&self) -> &str{
+        &self.metadata.common().title
+    }
+
+    pub fn get_slug(&self) -> String{
+        self.metadata.common().slug.to_string()
+    }
+
+    pub fn get_version(&self) -> usize{
+        self.metadata.common().version
+    }
+
+    pub fn set_version(&mut self, version: usize){
+        self.metadata.common_mut().version = version;
+    }
+
+    pub fn get_filename(&self) -> String{
+        self.metadata.get_filename()
+    }
+
+    /// Writes only `keys` to the existing source, leaving the rest of it as
+    /// it is. Falls back to `save` when there is no source yet.
+    pub async fn save_fields(&self, keys: &[&str]) -> Result<(), ContentError>{
+        let filename = self.get_filename();
+        match tokio::fs::read_to_string(&filename).await{
+            Ok(data) => self.write_fields(&filename, &data, keys).await,
+            Err(_) => self.write(&filename).await,
+        }
+    }
+
+    /// Writes the whole front matter. An existing source keeps the names,
+    /// order and comments of its keys (see `save_fields`).
+    pub async fn save(&self) -> Result<(), ContentError>{
+        let filename = self.get_filename();
+        match tokio::fs::read_to_string(&filename).await{
+            Ok(data) => {
+                let metadata = serde_yaml::to_value(&self.metadata)
+                    .map_err(|source| ContentError::Serialize{path: filename.clone(), source})?;
+                let keys: Vec<&str> = metadata.as_mapping()
+                    .map(|mapping| mapping.keys().filter_map(|key| key.as_str()).collect())
+                    .unwrap_or_default();
+                self.write_fields(&filename, &data, &keys).await
+            },
+            Err(_) => self.write(&filename).await,
+        }
+    }
+
+    /// `data` with `keys` set to their values in the metadata, or `None`
+    /// when none of them changes
+    fn update_fields(&self, filename: &str, data: &str, keys: &[&str]) -> Result<Option<String>, ContentError>{
+        let mut front_matter = FrontMatter::parse(data)
+            .ok_or_else(|| ContentError::FrontMatter{path: filename.to_string()})?;
+        let serialize_error = |source| ContentError::Serialize{path: filename.to_string(), source};
+        let metadata = serde_yaml::to_value(&self.metadata).map_err(serialize_error)?;
+        let mut changed = false;
+        for key in keys{
+            changed |= match metadata.get(key){
+                Some(value) => front_matter.set(key, value).map_err(serialize_error)?,
+                None => front_matter.remove(key),
+            };
+        }
+        Ok(Some(front_matter.to_string()).filter(|_| changed))
+    }
+
+    async fn write_fields(&self, filename: &str, data: &str, keys: &[&str]) -> Result<(), ContentError>{
+        if let Some(content) = self.update_fields(filename, data, keys)?{
+            info!("save {}", filename);
+            tokio::fs::write(filename, content)
+                .await
+                .map_err(|source| ContentError::Io{path: filename.to_string(), source})?;
+        }
+        Ok(())
+    }
+
+    /// Writes a new source
+    async fn write(&self, filename: &str) -> Result<(), ContentError>{
+        info!("save {}", filename);
+        let metadata = serde_yaml::to_string(&self.metadata)
+            .map_err(|source| ContentError::Serialize{path: filename.to_string(), source})?;
+        let mut content = String::new();
+        content.push_str("---\n");
+        content.push_str(&metadata);
+        content.push_str("---\n");
+        content.push_str(&self.content);
+        debug!("Content: {}", content);
+        if let Some(parent) = Path::new(filename).parent(){
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|source| ContentError::Io{path: filename.to_string(), source})?;
+        }
+        tokio::fs::write(filename, content)
+            .await
+            .map_err(|source| ContentError::Io{path: filename.to_string(), source})
+    }
+}
+
+/// Fills in the missing slug and excerpt of a source, touching nothing else,
+/// and renames it when its name does not match. Returns the name the source
+/// has (or would have, when `check` is set) if it needed any change.
+pub async fn fix<S: Schema>(filename: &str, check: bool) -> Result<Option<String>, ContentError>{
+    let filename = format!("{}/{}", S::DIR, filename);
+    let data = tokio::fs::read_to_string(&filename)
+        .await
+        .map_err(|source| ContentError::Io{path: filename.clone(), source})?;
+    let (document, _) = Document::<S>::parse(&filename, &data)?;
+    let mut front_matter = FrontMatter::parse(&data)
+        .ok_or_else(|| ContentError::FrontMatter{path: filename.clone()})?;
+    let common = document.metadata.common();
+    let serialize_error = |source| ContentError::Serialize{path: filename.clone(), source};
+    let mut changed = front_matter.set("slug", &common.slug).map_err(serialize_error)?;
+    changed |= front_matter.set("excerpt", &common.excerpt).map_err(serialize_error)?;
+    let target = document.get_filename();
+    if !changed && target == filename{
+        return Ok(None);
+    }
+    if !check{
+        if target != filename && tokio::fs::metadata(&target).await.is_ok(){
+            return Err(ContentError::Io{
+                path: target,
+                source: std::io::Error::from(std::io::ErrorKind::AlreadyExists),
+            });
+        }
+        tokio::fs::write(&target, front_matter.to_string())
+            .await
+            .map_err(|source| ContentError::Io{path: target.clone(), source})?;
+        if target != filename{
+            tokio::fs::remove_file(&filename)
+                .await
+                .map_err(|source| ContentError::Io{path: filename.clone(), source})?;
+        }
+    }
+    Ok(Some(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{
+        article::Metadata as ArticleMetadata,
+        content::{Document, ContentError},
+        episode::Episode,
+        site::Layout,
+        utils::get_unix_time,
+    };
+
+    #[test]
+    fn test_parse_fills_slug_and_excerpt(){
+        let data = "---\ntitle: Instalación de Pi-hole\ndate: 2020-05-15\ntags: [docker, pihole]\n---\nContenido del artículo";
+        let (article, save) = Document::<ArticleMetadata>::parse("posts/pihole.md", data).unwrap();
+        assert!(save);
+        let post = article.get_post();
+        assert_eq!(post.layout, Layout::POST);
+        assert_eq!(post.slug, "instalacion-de-pi-hole");
+        assert_eq!(post.excerpt, "Contenido del artículo");
+        assert_eq!(post.subject, vec!["docker", "pihole"]);
+        assert_eq!(post.date, get_unix_time("2020-05-15"));
+        assert_eq!(article.get_filename(), "posts/instalacion-de-pi-hole.md");
+    }
+
+    #[test]
+    fn test_parse_episode_datetime(){
+        let data = "---\nidentifier: pf-1\ntitle: Papá Friki 1\nsubject: linux\ndownloads: 10\nfilename: pf-1.mp3\ndatetime: 2020-05-15T10:00:00Z\nsize: 100\nlength: 60\nexcerpt: Primer episodio\nslug: papa-friki-1\n---\nDescripción";
+        let (episode, save) = Episode::parse("episodes/pf-1.md", data).unwrap();
+        assert!(!save);
+        let post = episode.get_post();
+        assert_eq!(post.layout, Layout::PODCAST);
+        assert_eq!(post.identifier, "pf-1");
+        assert_eq!(post.subject, vec!["linux"]);
+        assert_eq!(post.date.to_rfc3339(), "2020-05-15T10:00:00+00:00");
+        assert_eq!(episode.get_filename(), "episodes/pf-1.md");
+    }
+
+    #[test]
+    fn test_parse_errors(){
+        let result = Document::<ArticleMetadata>::parse("posts/empty.md", "Sin front matter");
+        assert!(matches!(result, Err(ContentError::FrontMatter{..})));
+        let result = Document::<ArticleMetadata>::parse("posts/bad.md", "---\ndate: 2020-05-15\n---\n");
+        assert!(matches!(result, Err(ContentError::Metadata{..})));
+        let (article, _) = Document::<ArticleMetadata>::parse("posts/undated.md", "---\ntitle: Sin fecha\n---\n").unwrap();
+        assert!(matches!(article.check_date("posts/undated.md"), Err(ContentError::Date{..})));
+    }
+
+    #[test]
+    fn test_save_keeps_keys(){
+        let data = "---\ntitle: Papá Friki 1\n# importado\ndatetime: 2020-05-15T10:00:00+00:00\ntags: [linux]\nidentifier: pf-1\ndownloads: 10\nfilename: pf-1.mp3\nsize: 100\nlength: 60\n---\nDescripción";
+        let (mut episode, _) = Episode::parse("episodes/pf-1.md", data).unwrap();
+        let keys = ["title", "date", "subject", "downloads"];
+        assert!(episode.update_fields("episodes/pf-1.md", data, &keys).unwrap().is_none());
+        episode.set_downloads(20);
+        let saved = episode.update_fields("episodes/pf-1.md", data, &keys).unwrap().unwrap();
+        assert!(saved.contains("# importado\ndatetime: 2020-05-15T10:00:00+00:00\ntags:"), "{}", saved);
+        assert!(saved.contains("downloads: 20"));
+        assert!(!saved.contains("\ndate:"));
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use std::{collections::BTreeMap, path::Path};
+use tokio::fs;
+use tracing::debug;
+
+use super::outbox::Outbox;
+
+/// Downloads of an item at some moment
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Snapshot{
+    pub date: DateTime<Utc>,
+    pub downloads: u64,
+}
+
+/// What the generator keeps between runs, in the `data` file of the
+/// configuration
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Data{
+    // identifier => snapshots, oldest first
+    #[serde(default)]
+    pub downloads: BTreeMap<String, Vec<Snapshot>>,
+    #[serde(default)]
+    pub outbox: Outbox,
+}
+
+impl Data{
+    /// Reads `path`. A missing file is an empty store.
+    pub async fn read(path: &str) -> Result<Self, std::io::Error>{
+        match fs::read_to_string(path).await{
+            Ok(content) => serde_json::from_str(&content).map_err(std::io::Error::from),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No data in {}", path);
+                Ok(Self::default())
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes the store to a temporary file first, so a failed write does
+    /// not lose the history
+    pub async fn save(&self, path: &str) -> Result<(), std::io::Error>{
+        if let Some(parent) = Path::new(path).parent(){
+            if !parent.as_os_str().is_empty(){
+                fs::create_dir_all(parent).await?;
+            }
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        let temporal = format!("{}.tmp", path);
+        fs::write(&temporal, content).await?;
+        fs::rename(&temporal, path).await
+    }
+
+    /// Adds the downloads of `identifier` at `date`. A snapshot of the same
+    /// day replaces the previous one.
+    pub fn record(&mut self, identifier: &str, date: DateTime<Utc>, downloads: u64){
+        let snapshots = self.downloads.entry(identifier.to_string()).or_default();
+        let snapshot = Snapshot{date, downloads};
+        match snapshots.last_mut(){
+            Some(last) if last.date.date_naive() == date.date_naive() => *last = snapshot,
+            _ => snapshots.push(snapshot),
+        }
+    }
+
+    pub fn get_snapshots(&self, identifier: &str) -> &[Snapshot]{
+        self.downloads.get(identifier).map(|snapshots| snapshots.as_slice()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Data;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_record(){
+        let mut data = Data::default();
+        data.record("pf-1", Utc.with_ymd_and_hms(2024, 5, 15, 8, 0, 0).unwrap(), 10);
+        data.record("pf-1", Utc.with_ymd_and_hms(2024, 5, 15, 20, 0, 0).unwrap(), 12);
+        data.record("pf-1", Utc.with_ymd_and_hms(2024, 5, 16, 8, 0, 0).unwrap(), 15);
+        let snapshots = data.get_snapshots("pf-1");
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].downloads, 12);
+        assert!(data.get_snapshots("pf-2").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_and_save(){
+        let dir = std::env::temp_dir().join(format!("aopodcast-data-{}", std::process::id()));
+        let path = dir.join("data/data.json").to_string_lossy().to_string();
+        assert!(Data::read(&path).await.unwrap().downloads.is_empty());
+        let mut data = Data::default();
+        data.record("pf-1", Utc.with_ymd_and_hms(2024, 5, 15, 8, 0, 0).unwrap(), 10);
+        data.save(&path).await.unwrap();
+        let data = Data::read(&path).await.unwrap();
+        assert_eq!(data.get_snapshots("pf-1")[0].downloads, 10);
+        tokio::fs::write(&path, "{").await.unwrap();
+        assert!(Data::read(&path).await.is_err());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
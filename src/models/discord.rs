@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::{
+    announcer::{Announcer, AnnounceError, BoxError, render, get_artwork_url, get_post_url},
+    site::{Post, Site},
+};
+
+/// Options of the Discord channel. The webhook url, a secret, comes from
+/// `DISCORD_WEBHOOK`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DiscordConfig{
+    pub template: String,
+    // name shown instead of the one of the webhook
+    pub username: Option<String>,
+    // colour of the embed, as 0xRRGGBB
+    pub color: Option<u32>,
+}
+
+impl Default for DiscordConfig{
+    fn default() -> Self{
+        Self{
+            template: "discord.html".to_string(),
+            username: None,
+            color: None,
+        }
+    }
+}
+
+pub struct Discord{
+    webhook: String,
+    template: String,
+    username: Option<String>,
+    color: Option<u32>,
+}
+
+impl Discord{
+    pub fn new(webhook: &str) -> Self{
+        Self{
+            webhook: webhook.to_string(),
+            template: DiscordConfig::default().template,
+            username: None,
+            color: None,
+        }
+    }
+
+    pub fn from_config(config: &DiscordConfig) -> Option<Self>{
+        let webhook = std::env::var("DISCORD_WEBHOOK").ok()?;
+        Some(Self{
+            template: config.template.clone(),
+            username: config.username.clone(),
+            color: config.color,
+            ..Self::new(&webhook)
+        })
+    }
+
+    /// Sends `content` with `embeds` and returns the id of the message
+    pub async fn send(&self, content: &str, embeds: &[Value]) -> Result<String, BoxError>{
+        let mut body = json!({
+            "content": content.trim(),
+            "embeds": embeds,
+        });
+        if let Some(username) = &self.username{
+            body["username"] = json!(username);
+        }
+        // wait for the message to be created to get its id
+        let message: Value = Client::new()
+            .post(&self.webhook)
+            .query(&[("wait", "true")])
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        message["id"].as_str()
+            .map(|id| id.to_string())
+            .ok_or_else(|| "Discord answered without the id of the message".into())
+    }
+
+    /// Rich embed with the title, excerpt and artwork of `post`
+    fn embed(&self, site: &Site, post: &Post) -> Value{
+        let mut embed = json!({
+            "title": post.title,
+            "url": get_post_url(site, post),
+            "description": post.excerpt,
+            "author": {"name": site.title},
+            "timestamp": post.date.to_rfc3339(),
+        });
+        if let Some(color) = self.color{
+            embed["color"] = json!(color);
+        }
+        if let Some(image) = get_artwork_url(site, post){
+            embed["thumbnail"] = json!({"url": image});
+        }
+        embed
+    }
+}
+
+#[async_trait]
+impl Announcer for Discord{
+    fn channel(&self) -> &str{
+        "discord"
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        let content = render(&self.template, site, post)?;
+        info!("Discord: {content}");
+        self.send(&content, &[self.embed(site, post)])
+            .await
+            .map_err(|source| AnnounceError::Send{channel: self.channel().to_string(), source})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Discord;
+    use crate::models::{announcer::Announcer, testing::{get_post, get_site}};
+    use serde_json::json;
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::{method, path, query_param}};
+
+    #[tokio::test]
+    async fn test_announce(){
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/webhooks/1/token"))
+            .and(query_param("wait", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"id": "1234"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut discord = Discord::new(&format!("{}/api/webhooks/1/token", server.uri()));
+        discord.color = Some(0xff6600);
+        let id = discord.announce(&get_site(), &get_post()).await.unwrap();
+        assert_eq!(id, "1234");
+        let body: serde_json::Value = server.received_requests().await.unwrap()[0].body_json().unwrap();
+        assert!(body["content"].as_str().unwrap().contains("Papá Friki 1"));
+        let embed = &body["embeds"][0];
+        assert_eq!(embed["title"], "Papá Friki 1");
+        assert_eq!(embed["url"], "https://atareao.es/podcast/papa-friki-1/");
+        assert_eq!(embed["color"], 0xff6600);
+        assert_eq!(embed["thumbnail"]["url"], "https://atareao.es/avatar.png");
+    }
+
+    #[tokio::test]
+    async fn test_error(){
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let discord = Discord::new(&server.uri());
+        assert!(discord.announce(&get_site(), &get_post()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_no_id(){
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .mount(&server)
+            .await;
+        let discord = Discord::new(&server.uri());
+        assert!(discord.announce(&get_site(), &get_post()).await.is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use async_trait::async_trait;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{header::ContentType, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+};
+use serde::{Serialize, Deserialize};
+use tracing::{debug, info, warn};
+
+use super::{
+    announcer::{Announcer, AnnounceError, BoxError, render},
+    site::{Post, Site},
+};
+
+/// Options of the email channel. The server comes from `SMTP_HOST` and, if
+/// it needs auth, `SMTP_USERNAME` and `SMTP_PASSWORD`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct EmailConfig{
+    pub template: String,
+    // `SMTP_FROM` when missing
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    // file with an address per line, `#` starts a comment. `SMTP_SUBSCRIBERS`
+    // when missing. It must not be committed, the repository is public.
+    pub subscribers: Option<String>,
+    // `SMTP_PORT`, or else 587
+    pub port: Option<u16>,
+    // false sends in plain text, only for local servers
+    pub starttls: bool,
+}
+
+impl Default for EmailConfig{
+    fn default() -> Self{
+        Self{
+            template: "email.html".to_string(),
+            from: None,
+            to: Vec::new(),
+            subscribers: None,
+            port: None,
+            starttls: true,
+        }
+    }
+}
+
+pub struct Email{
+    host: String,
+    port: u16,
+    starttls: bool,
+    credentials: Option<Credentials>,
+    from: String,
+    to: Vec<String>,
+    subscribers: Option<String>,
+    template: String,
+}
+
+impl Email{
+    pub fn new(host: &str, port: u16, from: &str) -> Self{
+        Self{
+            host: host.to_string(),
+            port,
+            starttls: true,
+            credentials: None,
+            from: from.to_string(),
+            to: Vec::new(),
+            subscribers: None,
+            template: EmailConfig::default().template,
+        }
+    }
+
+    pub fn from_config(config: &EmailConfig) -> Option<Self>{
+        let host = std::env::var("SMTP_HOST").ok()?;
+        let from = match &config.from{
+            Some(from) => from.to_owned(),
+            None => std::env::var("SMTP_FROM").ok()?,
+        };
+        let port = config.port
+            .or_else(|| std::env::var("SMTP_PORT").ok().and_then(|port| port.parse().ok()))
+            .unwrap_or(587);
+        let credentials = match (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")){
+            (Ok(username), Ok(password)) => Some(Credentials::new(username, password)),
+            _ => None,
+        };
+        Some(Self{
+            starttls: config.starttls,
+            credentials,
+            to: config.to.clone(),
+            subscribers: config.subscribers.clone()
+                .or_else(|| std::env::var("SMTP_SUBSCRIBERS").ok()),
+            template: config.template.clone(),
+            ..Self::new(&host, port, &from)
+        })
+    }
+
+    /// Addresses in `to` and in the subscribers file, without repetitions
+    async fn get_recipients(&self) -> Result<Vec<String>, BoxError>{
+        let mut recipients = self.to.clone();
+        if let Some(subscribers) = &self.subscribers{
+            let content = tokio::fs::read_to_string(subscribers).await?;
+            recipients.extend(content.lines()
+                .map(|line| line.split('#').next().unwrap_or_default().trim())
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_string()));
+        }
+        recipients.sort();
+        recipients.dedup();
+        Ok(recipients)
+    }
+
+    /// Sends `html`, and its plain text version, to every recipient in
+    /// blind copy. Returns the `Message-ID` of the email.
+    pub async fn send(&self, subject: &str, html: &str, id: &str) -> Result<String, BoxError>{
+        let recipients = self.get_recipients().await?;
+        if recipients.is_empty(){
+            return Err("no recipients".into());
+        }
+        let from: Mailbox = self.from.parse()?;
+        let message_id = format!("<{}@{}>", id, from.email.domain());
+        let mut builder = Message::builder()
+            .from(from.clone())
+            .to(from)
+            .subject(subject)
+            .message_id(Some(message_id.clone()));
+        let mut valid = 0;
+        for recipient in &recipients{
+            // one typo in the subscribers file must not hold up the rest
+            match recipient.parse::<Mailbox>(){
+                Ok(mailbox) => {
+                    builder = builder.bcc(mailbox);
+                    valid += 1;
+                },
+                Err(err) => warn!("Skipping invalid address {}. {:#}", recipient, err),
+            }
+        }
+        if valid == 0{
+            return Err("no valid recipients".into());
+        }
+        let text = html2md::parse_html(html);
+        let message = builder.multipart(MultiPart::alternative()
+            .singlepart(SinglePart::builder()
+                .header(ContentType::TEXT_PLAIN)
+                .body(text))
+            .singlepart(SinglePart::builder()
+                .header(ContentType::TEXT_HTML)
+                .body(html.to_string())))?;
+        let mut transport = if self.starttls{
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?
+        }else{
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host)
+        }.port(self.port);
+        if let Some(credentials) = &self.credentials{
+            transport = transport.credentials(credentials.clone());
+        }
+        debug!("Sending {} to {} recipients", message_id, valid);
+        transport.build().send(message).await?;
+        Ok(message_id)
+    }
+}
+
+#[async_trait]
+impl Announcer for Email{
+    fn channel(&self) -> &str{
+        "email"
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        let html = render(&self.template, site, post)?;
+        let subject = format!("{}: {}", site.title, post.title);
+        // the same on every retry, so clients can drop a repeated email
+        let message_id = self.send(&subject, &html, &post.identifier)
+            .await
+            .map_err(|source| AnnounceError::Send{channel: self.channel().to_string(), source})?;
+        info!("Email {} sent", message_id);
+        Ok(message_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Email;
+    use crate::models::{announcer::Announcer, testing::{get_post, get_site}};
+    use std::sync::{Arc, Mutex};
+    use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::TcpListener};
+
+    /// Plain SMTP server that accepts everything and keeps the commands and
+    /// data it receives
+    async fn start_sink() -> (u16, Arc<Mutex<String>>){
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(String::new()));
+        let log = received.clone();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+            let mut data = false;
+            while let Ok(Some(line)) = lines.next_line().await{
+                log.lock().unwrap().push_str(&format!("{}\n", line));
+                let answer: &[u8] = if data{
+                    if line != "."{
+                        continue;
+                    }
+                    data = false;
+                    b"250 queued\r\n"
+                }else if line.starts_with("EHLO"){
+                    b"250 sink\r\n"
+                }else if line == "DATA"{
+                    data = true;
+                    b"354 go on\r\n"
+                }else if line == "QUIT"{
+                    writer.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                }else{
+                    b"250 ok\r\n"
+                };
+                writer.write_all(answer).await.unwrap();
+            }
+        });
+        (port, received)
+    }
+
+    #[tokio::test]
+    async fn test_announce(){
+        let (port, received) = start_sink().await;
+        let subscribers = tempfile::NamedTempFile::new().unwrap();
+        tokio::fs::write(subscribers.path(), "# oyentes\nana@example.com\n\nluis.example.com\nluis@example.com # desde 2023\n")
+            .await
+            .unwrap();
+        let mut email = Email::new("127.0.0.1", port, "Papá Friki <podcast@example.com>");
+        email.starttls = false;
+        email.to = vec!["ana@example.com".to_string()];
+        email.subscribers = Some(subscribers.path().to_string_lossy().to_string());
+        let id = email.announce(&get_site(), &get_post()).await.unwrap();
+        assert_eq!(id, "<pf-1@example.com>");
+        let received = received.lock().unwrap().clone();
+        assert!(received.contains("MAIL FROM:<podcast@example.com>"), "{}", received);
+        assert!(received.contains("RCPT TO:<ana@example.com>"));
+        assert!(received.contains("RCPT TO:<luis@example.com>"));
+        assert_eq!(received.matches("RCPT TO:<ana@example.com>").count(), 1);
+        // the malformed line is skipped
+        assert!(!received.contains("luis.example.com"));
+        assert!(received.contains("Content-Type: text/plain"));
+        assert!(received.contains("Content-Type: text/html"));
+        // recipients go in blind copy
+        assert!(!received.contains("Bcc:"));
+    }
+
+    #[tokio::test]
+    async fn test_no_recipients(){
+        let email = Email::new("127.0.0.1", 1, "podcast@example.com");
+        assert!(email.announce(&get_site(), &get_post()).await.is_err());
+    }
+}
@@ -1,191 +1,160 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize, Deserializer, de};
-use tracing::{debug, info, error};
-use gray_matter::{Matter, engine::YAML};
-use comrak::{markdown_to_html, ComrakOptions};
-use std::{fmt, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::debug;
 
 use super::{
     doc::Doc,
-    site::{Post, Layout},
+    site::{Post, Layout, EpisodeType},
     metadata::AOMetadata,
     mp3metadata::Mp3Metadata,
+    content::{Common, Document, Schema},
+    chapters::{Chapter, parse_chapters, format_timestamp},
+    person::Credit,
     utils::{
         get_slug,
-        get_excerpt
+        get_excerpt,
     },
 };
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Metadata{
+    #[serde(flatten)]
+    pub common: Common,
     // from doc
     #[serde(default = "default_number")]
     pub number: usize,
     pub identifier: String,
-    pub title: String,
-    #[serde(deserialize_with = "string_or_seq_string")]
-    pub subject: Vec<String>,
     //pub description: String,
     pub downloads: u64,
     // from mp3 metadata
     pub filename: String,
-    #[serde(default = "get_default_datetime")]
-    pub datetime: Option<DateTime<Utc>>,
-    #[serde(default = "get_default_version")]
-    pub version: usize,
     pub size: u64,
     pub length: u64,
-    pub excerpt: String,
     //pub comment: String,
-    // more
-    pub slug: String,
+    // assigned once and never recomputed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub season: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub episode: Option<usize>,
+    #[serde(default)]
+    pub episode_type: EpisodeType,
+    // taken from the timestamps in the show notes when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chapters: Vec<Chapter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub people: Vec<Credit>,
+    // artwork, an url or a path in the site; `site.avatar` when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    // `true` while the announcement waits for the episode to be published,
+    // `false` once queued in the outbox. When missing, only episodes with a
+    // `publish_at` are announced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announce: Option<bool>,
+    // channel => id of the message it was announced with
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub announcements: BTreeMap<String, String>,
 }
 
-fn get_default_datetime() -> Option<DateTime<Utc>>{
-    None
-}
-
-fn get_default_version() -> usize{
+fn default_number() -> usize {
     0
 }
 
+impl Schema for Metadata{
+    const DIR: &'static str = "episodes";
+    const LAYOUT: Layout = Layout::PODCAST;
 
-fn string_or_seq_string<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
-    where D: Deserializer<'de>
-{
-    struct StringOrVec(PhantomData<Vec<String>>);
-
-    impl<'de> de::Visitor<'de> for StringOrVec {
-        type Value = Vec<String>;
-
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("string or list of strings")
-        }
+    fn common(&self) -> &Common{
+        &self.common
+    }
 
-        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
-            where E: de::Error
-        {
-            Ok(vec![value.to_owned()])
-        }
+    fn common_mut(&mut self) -> &mut Common{
+        &mut self.common
+    }
 
-        fn visit_seq<S>(self, visitor: S) -> Result<Self::Value, S::Error>
-            where S: de::SeqAccess<'de>
-        {
-            Deserialize::deserialize(de::value::SeqAccessDeserializer::new(visitor))
-        }
+    fn get_filename(&self) -> String {
+        format!("{}/{}.md", Self::DIR, self.identifier)
     }
 
-    deserializer.deserialize_any(StringOrVec(PhantomData))
+    fn fill_post(&self, content: &str, post: &mut Post){
+        post.identifier = self.identifier.clone();
+        post.filename = self.filename.clone();
+        post.length = self.length;
+        post.size = self.size;
+        post.number = self.number;
+        post.downloads = self.downloads;
+        post.season = self.season;
+        post.episode = self.episode;
+        post.episode_type = Some(self.episode_type);
+        post.people = self.people.clone();
+        post.image = self.image.clone();
+        post.chapters = if self.chapters.is_empty(){
+            parse_chapters(content)
+        }else{
+            self.chapters.iter()
+                .map(|chapter| Chapter{
+                    time: format_timestamp(chapter.start),
+                    ..chapter.clone()
+                })
+                .collect()
+        };
+    }
 }
 
+pub type Episode = Document<Metadata>;
 
-fn default_number() -> usize {
-    0
-}
+impl Episode{
+    pub fn set_datetime(&mut self, datetime: DateTime<Utc>){
+        self.metadata.common.date = Some(datetime);
+    }
 
-impl Metadata{
-    pub fn get_filename(&self) -> String {
-        format!("episodes/{}.md", self.identifier)
+    /// New and scheduled episodes wait here until the first run they are
+    /// published in
+    pub fn is_pending_announcement(&self) -> bool{
+        let scheduled = self.metadata.common.publish_at.is_some();
+        self.metadata.announce.unwrap_or(scheduled) && self.is_published()
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Episode{
-    metadata: Metadata,
-    pub content: String,
-}
+    pub fn set_announced(&mut self){
+        self.metadata.announce = Some(false);
+    }
 
-impl Episode{
-    pub fn get_post(&self) -> Post{
-        let content = markdown_to_html(&self.content, &ComrakOptions::default());
-        Post{
-            layout: Layout::PODCAST,
-            slug: self.metadata.slug.clone(),
-            excerpt: self.metadata.excerpt.clone(),
-            title: self.metadata.title.clone(),
-            content,
-            subject: self.metadata.subject.clone(),
-            date: self.metadata.datetime.unwrap(),
-            version: self.metadata.version,
-            identifier: self.metadata.identifier.clone(),
-            filename: self.metadata.filename.clone(),
-            length: self.metadata.length,
-            size: self.metadata.size,
-            number: self.metadata.number,
-            downloads: self.metadata.downloads,
-        }
+    pub fn is_announced_in(&self, channel: &str) -> bool{
+        self.metadata.announcements.contains_key(channel)
     }
 
-    pub fn set_version(&mut self, version: usize){
-        self.metadata.version = version
+    pub fn set_announcement(&mut self, channel: &str, id: &str){
+        self.metadata.announcements.insert(channel.to_string(), id.to_string());
     }
 
-    pub fn get_version(&self) -> usize{
-        self.metadata.version
+    pub fn get_identifier(&self) -> &str{
+        &self.metadata.identifier
     }
 
-    pub fn set_datetime(&mut self, datetime: DateTime<Utc>){
-        self.metadata.datetime = Some(datetime);
+    pub fn get_season(&self) -> Option<usize>{
+        self.metadata.season
     }
 
-    pub async fn new(filename: &str) -> Result<Self, serde_json::Error>{
-        let mut save = false;
-        let filename = format!("episodes/{}", filename);
-        debug!("Filename: {}", filename);
-        let data = tokio::fs::read_to_string(&filename)
-            .await
-            .unwrap();
-        let matter = Matter::<YAML>::new();
-        let result = matter.parse(&data);
-        let mut metadata: Metadata = result.data.unwrap().deserialize()?;
-        debug!("Metadata: {:?}", &metadata);
-        if metadata.slug.is_empty(){
-            debug!("Is empty");
-            metadata.slug = get_slug(&metadata.title);
-            save = true;
-        }
-        if metadata.excerpt.is_empty(){
-            metadata.excerpt = match result.excerpt {
-                Some(excerpt) => {
-                    save = true;
-                    excerpt
-                },
-                None => get_excerpt(&result.content).to_string(),
-            };
-        }
-        debug!("Metadata: {:?}", &metadata);
-        let episode = Self{
-            metadata,
-            content: result.content,
-        };
-        if save{
-            match episode.save().await{
-                Ok(_) => {
-                    info!("Saved article {}", episode.get_filename());
-                    if filename != episode.get_filename(){
-                        match tokio::fs::remove_file(&filename).await{
-                            Ok(_) => info!("Removed {}", &filename),
-                            Err(e) => error!("Cant remove {}. {}", &filename, e),
-                        }
-                    }
-                },
-                Err(_) => error!("Cant save article {}", episode.get_filename()),
-            }
-        }
-        Ok(episode)
+    pub fn get_episode(&self) -> Option<usize>{
+        self.metadata.episode
+    }
+
+    pub fn set_episode(&mut self, season: Option<usize>, episode: usize){
+        self.metadata.season = season;
+        self.metadata.episode = Some(episode);
     }
 
-    #[allow(dead_code)]
-    pub fn get_title(&self) -> &str{
-        &self.metadata.title
+    pub fn get_episode_type(&self) -> EpisodeType{
+        self.metadata.episode_type
     }
 
-    pub fn get_filename(&self) -> String{
-        self.metadata.get_filename()
+    pub fn get_image(&self) -> Option<&str>{
+        self.metadata.image.as_deref()
     }
 
-    pub fn get_slug(&self) -> String{
-        self.metadata.slug.to_string()
+    pub fn set_image(&mut self, image: Option<String>){
+        self.metadata.image = image;
     }
 
     pub fn get_downloads(&self) -> u64{
@@ -196,18 +165,6 @@ impl Episode{
         self.metadata.downloads = downloads;
     }
 
-    pub async fn save(&self)-> tokio::io::Result<()>{
-        info!("save {}", &self.get_filename());
-        let mut content = String::new();
-        debug!("Metadata: {}", &serde_yaml::to_string(&self.metadata).unwrap());
-        content.push_str("---\n");
-        content.push_str(&serde_yaml::to_string(&self.metadata).unwrap());
-        content.push_str("---\n");
-        content.push_str(&self.content);
-        debug!("Content: {}", content);
-        tokio::fs::write(self.get_filename(), content).await
-    }
-
     pub fn combine(doc: &Doc, aometadata: &AOMetadata, mp3: &Mp3Metadata) -> Episode{
         let title = if mp3.title.is_empty(){
             doc.get_identifier()
@@ -222,18 +179,30 @@ impl Episode{
         };
         debug!("Comment: {}", &comment);
         let metadata = Metadata{
+            common: Common{
+                title: title.to_string(),
+                date: Some(doc.get_datetime()),
+                excerpt: comment.to_owned(),
+                slug: get_slug(title),
+                version: doc.get_version(),
+                subject: doc.get_subject(),
+                draft: false,
+                publish_at: None,
+            },
             number: doc.get_number(),
             identifier: doc.get_identifier().to_string(),
-            subject: doc.get_subject(),
             downloads: doc.get_downloads(),
-            datetime: Some(doc.get_datetime()),
-            version: doc.get_version(),
-            title: title.to_string(),
-            excerpt: comment.to_owned(),
             filename: mp3.filename.to_string(),
             size: mp3.size,
             length: mp3.length,
-            slug: get_slug(title),
+            season: None,
+            episode: None,
+            episode_type: EpisodeType::Full,
+            chapters: Vec::new(),
+            people: Vec::new(),
+            image: None,
+            announce: Some(true),
+            announcements: BTreeMap::new(),
         };
         Self{
             metadata,
@@ -242,6 +211,46 @@ impl Episode{
     }
 }
 
+/// Numbers the full episodes that have no number yet following their date
+/// within the season, the way new episodes are numbered, without taking a
+/// number already in use. Returns the positions of the episodes it numbered.
+pub fn number(episodes: &mut [Episode]) -> Vec<usize>{
+    let mut used: HashMap<Option<usize>, HashSet<usize>> = HashMap::new();
+    for episode in episodes.iter(){
+        if let Some(number) = episode.get_episode(){
+            used.entry(episode.get_season()).or_default().insert(number);
+        }
+    }
+    let mut order: Vec<usize> = (0..episodes.len())
+        .filter(|&index| episodes[index].get_episode_type() == EpisodeType::Full)
+        .collect();
+    order.sort_by_key(|&index| {
+        let common = &episodes[index].metadata.common;
+        (episodes[index].get_season(), common.date.or(common.publish_at))
+    });
+    let mut numbered = Vec::new();
+    let mut last: HashMap<Option<usize>, usize> = HashMap::new();
+    for index in order{
+        let episode = &mut episodes[index];
+        let season = episode.get_season();
+        let last = last.entry(season).or_default();
+        match episode.get_episode(){
+            Some(number) => *last = number,
+            None => {
+                let used = used.entry(season).or_default();
+                *last += 1;
+                while used.contains(last){
+                    *last += 1;
+                }
+                used.insert(*last);
+                episode.set_episode(season, *last);
+                numbered.push(index);
+            },
+        }
+    }
+    numbered
+}
+
 #[cfg(test)]
 mod tests {
     use tracing_subscriber::{
@@ -255,16 +264,63 @@ mod tests {
 
     #[tokio::test]
     async fn test2(){
-        tracing_subscriber::registry()
+        let _ = tracing_subscriber::registry()
             .with(EnvFilter::from_str("debug").unwrap())
             .with(tracing_subscriber::fmt::layer())
-            .init();
+            .try_init();
 
-        let episode = Episode::new("pihole.md").await.unwrap();
-        debug!("Title: {}", episode.metadata.title);
+        let data = "---\nidentifier: pf-1\ntitle: Papá Friki 1\nsubject: [linux]\ndownloads: 10\nfilename: pf-1.mp3\nsize: 100\nlength: 60\nexcerpt: ''\nslug: ''\n---\nDescripción del episodio";
+        let (episode, save) = Episode::parse("episodes/pf-1.md", data).unwrap();
+        debug!("Title: {}", episode.metadata.common.title);
         debug!("=========================");
         debug!("{:?}", episode);
         debug!("=========================");
-        assert!(!episode.metadata.title.is_empty());
+        assert!(save);
+        assert!(!episode.metadata.common.title.is_empty());
+        assert_eq!(episode.get_slug(), "papa-friki-1");
+        assert!(!episode.is_pending_announcement());
+    }
+
+    #[test]
+    fn test_scheduled_announcement(){
+        let future = (chrono::Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+        let past = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+        let data = "---\nidentifier: pf-1\ntitle: Papá Friki 1\ndownloads: 10\nfilename: pf-1.mp3\nsize: 100\nlength: 60\n";
+        let parse = |extra: &str| {
+            Episode::parse("episodes/pf-1.md", &format!("{}{}---\nDescripción", data, extra)).unwrap().0
+        };
+        // waits for publish_at, even when the file was written before
+        assert!(!parse(&format!("publish_at: {}\nannounce: true\n", future)).is_pending_announcement());
+        assert!(!parse(&format!("publish_at: {}\n", future)).is_pending_announcement());
+        assert!(parse(&format!("publish_at: {}\n", past)).is_pending_announcement());
+        assert!(parse("announce: true\n").is_pending_announcement());
+        assert!(!parse("announce: true\ndraft: true\n").is_pending_announcement());
+        let mut episode = parse(&format!("publish_at: {}\n", past));
+        episode.set_announced();
+        assert!(!episode.is_pending_announcement());
+    }
+
+    #[test]
+    fn test_number(){
+        let parse = |identifier: &str, extra: &str| {
+            let data = format!("---\nidentifier: {}\ntitle: {}\ndownloads: 10\nfilename: {}.mp3\nsize: 100\nlength: 60\n{}---\n", identifier, identifier, identifier, extra);
+            Episode::parse("episodes/pf.md", &data).unwrap().0
+        };
+        let mut episodes = vec![
+            parse("pf-3", "date: 2024-03-01\n"),
+            parse("pf-1", "date: 2024-01-01\n"),
+            parse("pf-2", "date: 2024-02-01\nepisode: 2\n"),
+            parse("pf-4", "date: 2024-04-01\nepisode: 4\n"),
+            parse("pf-5", "date: 2024-05-01\n"),
+            parse("extra", "date: 2024-02-15\nepisode_type: bonus\n"),
+            parse("t2-1", "date: 2023-01-01\nseason: 2\n"),
+        ];
+        let numbered = super::number(&mut episodes);
+        assert_eq!(numbered.len(), 4);
+        let numbers: Vec<Option<usize>> = episodes.iter().map(|episode| episode.get_episode()).collect();
+        // pf-3 would be 3 after pf-2, pf-5 skips 4, which pf-4 already has
+        assert_eq!(numbers, vec![Some(3), Some(1), Some(2), Some(4), Some(5), None, Some(1)]);
+        assert_eq!(episodes[6].get_season(), Some(2));
+        assert!(super::number(&mut episodes).is_empty());
     }
 }
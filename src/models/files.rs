@@ -0,0 +1,93 @@
+use regex::Regex;
+
+const IMAGES: [&str; 3] = ["jpg", "jpeg", "png"];
+const ITEM_TILE: &str = "Item Tile";
+
+/// Entry of the `<identifier>_files.xml` listing of an archive.org item
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveFile{
+    pub name: String,
+    pub source: String,
+    pub format: String,
+}
+
+impl ArchiveFile{
+    pub fn new(content: &str) -> Vec<ArchiveFile>{
+        let re = Regex::new(r#"(?s)<file name="([^"]*)" source="([^"]*)"[^>]*?(?:/>|>(.*?)</file>)"#).unwrap();
+        let format = Regex::new(r"<format>([^<]*)</format>").unwrap();
+        re.captures_iter(content)
+            .map(|caps| ArchiveFile{
+                name: html_escape::decode_html_entities(&caps[1]).to_string(),
+                source: caps[2].to_string(),
+                format: caps.get(3)
+                    .and_then(|body| format.captures(body.as_str()))
+                    .map(|format| format[1].to_string())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    pub fn get_extension(&self) -> String{
+        match self.name.rfind('.'){
+            Some(pos) => self.name[pos + 1..].to_lowercase(),
+            None => "".to_string(),
+        }
+    }
+}
+
+/// Cover of the item: an uploaded JPEG or PNG, or else the tile archive.org
+/// makes for the item page
+pub fn find_image(files: &[ArchiveFile]) -> Option<&ArchiveFile>{
+    files.iter()
+        .find(|file| {
+            file.source == "original"
+                && file.format != ITEM_TILE
+                && IMAGES.contains(&file.get_extension().as_str())
+        })
+        .or_else(|| files.iter().find(|file| file.format == ITEM_TILE))
+}
+
+/// First WebVTT or SubRip file of the item
+pub fn find_transcript(files: &[ArchiveFile]) -> Option<&ArchiveFile>{
+    ["vtt", "srt"].iter()
+        .find_map(|extension| files.iter().find(|file| &file.get_extension() == extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveFile, find_image, find_transcript};
+
+    const FILES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<files>
+  <file name="pf-1.mp3" source="original">
+    <mtime>1715767200</mtime>
+    <size>54300000</size>
+    <format>VBR MP3</format>
+  </file>
+  <file name="pf-1.srt" source="original">
+    <format>SubRip</format>
+  </file>
+  <file name="pf-1_meta.xml" source="original"/>
+  <file name="__ia_thumb.jpg" source="original">
+    <format>Item Tile</format>
+  </file>
+</files>"#;
+
+    #[test]
+    fn test_files(){
+        let files = ArchiveFile::new(FILES);
+        assert_eq!(files.len(), 4);
+        assert_eq!(files[0].format, "VBR MP3");
+        assert_eq!(files[2].name, "pf-1_meta.xml");
+        assert_eq!(find_transcript(&files).unwrap().name, "pf-1.srt");
+        assert_eq!(find_image(&files).unwrap().name, "__ia_thumb.jpg");
+    }
+
+    #[test]
+    fn test_original_image(){
+        let files = ArchiveFile::new(r#"<file name="__ia_thumb.jpg" source="original"><format>Item Tile</format></file>
+<file name="portada.PNG" source="original"><format>PNG</format></file>"#);
+        assert_eq!(find_image(&files).unwrap().name, "portada.PNG");
+        assert!(find_image(&files[..0]).is_none());
+    }
+}
@@ -0,0 +1,199 @@
+use serde::Serialize;
+use serde_yaml::Value;
+
+const DELIMITER: &str = "---";
+
+/// Keys written under a different name in older sources
+const ALIASES: &[(&str, &str)] = &[
+    ("date", "datetime"),
+    ("subject", "tags"),
+];
+
+/// Line based editor for the YAML front matter of a Markdown source. Only
+/// the keys that are set or removed change, so key order, comments and the
+/// body stay as they were.
+#[derive(Debug)]
+pub struct FrontMatter{
+    lines: Vec<String>,
+    body: String,
+}
+
+impl FrontMatter{
+    pub fn parse(data: &str) -> Option<Self>{
+        let mut rest = data.strip_prefix(DELIMITER)?;
+        rest = rest.strip_prefix('\r').unwrap_or(rest);
+        rest = rest.strip_prefix('\n')?;
+        let mut lines = Vec::new();
+        let mut position = 0;
+        for line in rest.split_inclusive('\n'){
+            position += line.len();
+            let content = line.trim_end_matches(['\n', '\r']);
+            if content.trim_end() == DELIMITER{
+                return Some(Self{
+                    lines,
+                    body: rest[position..].to_string(),
+                });
+            }
+            lines.push(content.to_string());
+        }
+        None
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value>{
+        let key = self.find_key(key)?;
+        let (start, end) = self.find(&key)?;
+        let mut raw = self.lines[start][key.len() + 1..].to_string();
+        for line in &self.lines[start + 1..end]{
+            raw.push('\n');
+            raw.push_str(line);
+        }
+        serde_yaml::from_str(&raw).ok()
+    }
+
+    /// Sets `key` to `value` rewriting only its lines. Returns whether the
+    /// source changed.
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> Result<bool, serde_yaml::Error>{
+        let value = serde_yaml::to_value(value)?;
+        let key = self.find_key(key).unwrap_or_else(|| key.to_string());
+        if self.get(&key).as_ref() == Some(&value){
+            return Ok(false);
+        }
+        let lines = render(&key, &value)?;
+        match self.find(&key){
+            Some((start, end)) => {
+                self.lines.splice(start..end, lines);
+            },
+            None => self.lines.extend(lines),
+        }
+        Ok(true)
+    }
+
+    /// Removes `key`. Returns whether the source changed.
+    pub fn remove(&mut self, key: &str) -> bool{
+        let key = match self.find_key(key){
+            Some(key) => key,
+            None => return false,
+        };
+        match self.find(&key){
+            Some((start, end)) => {
+                self.lines.drain(start..end);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// The name `key` has in this source, looking at its aliases too
+    fn find_key(&self, key: &str) -> Option<String>{
+        let mut names = vec![key];
+        for (name, alias) in ALIASES{
+            if *name == key{
+                names.push(alias);
+            }
+        }
+        names.into_iter()
+            .find(|name| self.find(name).is_some())
+            .map(|name| name.to_string())
+    }
+
+    /// First and next to last line of a top level key
+    fn find(&self, key: &str) -> Option<(usize, usize)>{
+        let start = self.lines.iter().position(|line| {
+            line.strip_prefix(key)
+                .map(|rest| rest.starts_with(':'))
+                .unwrap_or(false)
+        })?;
+        let mut end = start + 1;
+        while end < self.lines.len() && is_continuation(&self.lines[end]){
+            end += 1;
+        }
+        Some((start, end))
+    }
+}
+
+impl std::fmt::Display for FrontMatter{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", DELIMITER)?;
+        for line in &self.lines{
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, "{}", DELIMITER)?;
+        write!(f, "{}", self.body)
+    }
+}
+
+fn is_continuation(line: &str) -> bool{
+    line.starts_with(' ') || line.starts_with('\t') || (line.starts_with('-') && line != DELIMITER)
+}
+
+fn render(key: &str, value: &Value) -> Result<Vec<String>, serde_yaml::Error>{
+    let yaml = serde_yaml::to_string(value)?;
+    let nested = match value{
+        Value::Sequence(items) => !items.is_empty(),
+        Value::Mapping(items) => !items.is_empty(),
+        _ => false,
+    };
+    if nested{
+        let mut lines = vec![format!("{}:", key)];
+        lines.extend(yaml.lines().map(|line| format!("  {}", line)));
+        Ok(lines)
+    }else{
+        Ok(vec![format!("{}: {}", key, yaml.trim_end())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrontMatter;
+
+    const SOURCE: &str = "---\n# Episodio
+title: Papá Friki 1
+datetime: 2020-05-15T10:00:00Z
+subject:
+- linux
+- docker
+downloads: 10
+announce: true
+---
+# Descripción
+
+Texto del episodio
+";
+
+    #[test]
+    fn test_unchanged(){
+        let front_matter = FrontMatter::parse(SOURCE).unwrap();
+        assert_eq!(front_matter.to_string(), SOURCE);
+        assert_eq!(front_matter.get("downloads").unwrap().as_u64(), Some(10));
+    }
+
+    #[test]
+    fn test_set_and_remove(){
+        let mut front_matter = FrontMatter::parse(SOURCE).unwrap();
+        assert!(!front_matter.set("downloads", &10).unwrap());
+        assert!(front_matter.set("downloads", &25).unwrap());
+        assert!(front_matter.set("date", &"2020-05-16T10:00:00Z").unwrap());
+        assert!(front_matter.set("subject", &vec!["linux"]).unwrap());
+        assert!(front_matter.set("slug", &"papa-friki-1").unwrap());
+        assert!(front_matter.remove("announce"));
+        assert!(!front_matter.remove("announce"));
+        assert_eq!(front_matter.to_string(), "---\n# Episodio
+title: Papá Friki 1
+datetime: 2020-05-16T10:00:00Z
+subject:
+  - linux
+downloads: 25
+slug: papa-friki-1
+---
+# Descripción
+
+Texto del episodio
+");
+    }
+
+    #[test]
+    fn test_no_front_matter(){
+        assert!(FrontMatter::parse("# Título\n").is_none());
+        assert!(FrontMatter::parse("---\ntitle: Sin cerrar\n").is_none());
+    }
+}
@@ -0,0 +1,275 @@
+use comrak::{
+    markdown_to_html_with_plugins,
+    plugins::syntect::SyntectAdapter,
+    Options,
+    Plugins,
+};
+use minijinja::{context, value::{StructObject, Value}};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use std::{collections::HashMap, sync::Arc};
+use tracing::{error, warn};
+
+use super::{site::{Post, Site}, shortcodes::{self, Shortcode}, ENV};
+
+/// Themes bundled with the highlighter
+const HIGHLIGHT_THEMES: [&str; 7] = [
+    "base16-ocean.dark",
+    "base16-eighties.dark",
+    "base16-mocha.dark",
+    "base16-ocean.light",
+    "InspiredGitHub",
+    "Solarized (dark)",
+    "Solarized (light)",
+];
+
+/// Options chosen in the configuration. Set them before the first render.
+pub static OPTIONS: OnceCell<Markdown> = OnceCell::new();
+
+static HIGHLIGHTER: Lazy<Option<SyntectAdapter>> = Lazy::new(|| {
+    let options = OPTIONS.get().cloned().unwrap_or_default();
+    if !options.highlight{
+        return None;
+    }
+    let theme = options.highlight_theme.filter(|theme| {
+        let known = HIGHLIGHT_THEMES.contains(&theme.as_str());
+        if !known{
+            warn!("Unknown highlight theme {}. Using css classes", theme);
+        }
+        known
+    });
+    Some(SyntectAdapter::new(theme.as_deref()))
+});
+
+/// Headings as comrak writes them with `header_ids`
+static HEADING: Lazy<Regex> = Lazy::new(|| Regex::new(
+    r#"(?s)<h([1-6])><a href="[^"]*" aria-hidden="true" class="anchor" id="([^"]*)"></a>(.*?)</h[1-6]>"#
+).unwrap());
+static TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// The `markdown` section of the configuration. Every extension is off
+/// unless enabled, as comrak does by default.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Markdown{
+    pub table: bool,
+    pub strikethrough: bool,
+    pub footnotes: bool,
+    pub autolink: bool,
+    pub tasklist: bool,
+    pub superscript: bool,
+    pub description_lists: bool,
+    pub smart: bool,
+    // raw html in the Markdown is left as is instead of omitted
+    #[serde(rename = "unsafe")]
+    pub unsafe_html: bool,
+    pub header_ids: bool,
+    // table of contents in `post.toc`, needs `header_ids`
+    pub toc: bool,
+    pub highlight: bool,
+    // inline styles from this theme, css classes when empty
+    pub highlight_theme: Option<String>,
+}
+
+impl Markdown{
+    fn get_options(&self) -> Options{
+        let mut options = Options::default();
+        options.extension.table = self.table;
+        options.extension.strikethrough = self.strikethrough;
+        options.extension.footnotes = self.footnotes;
+        options.extension.autolink = self.autolink;
+        options.extension.tasklist = self.tasklist;
+        options.extension.superscript = self.superscript;
+        options.extension.description_lists = self.description_lists;
+        options.extension.header_ids = if self.header_ids{
+            Some("".to_string())
+        }else{
+            None
+        };
+        options.parse.smart = self.smart;
+        options.render.unsafe_ = self.unsafe_html;
+        options
+    }
+}
+
+/// Entry of the table of contents
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Heading{
+    pub level: u8,
+    pub title: String,
+    pub id: String,
+}
+
+pub const SHORTCODES_DIR: &str = "shortcodes";
+
+/// Posts by slug, for shortcodes to look up as `posts[slug]`
+#[derive(Debug, Clone)]
+struct PostIndex(Arc<HashMap<String, Post>>);
+
+impl StructObject for PostIndex{
+    fn get_field(&self, name: &str) -> Option<Value>{
+        self.0.get(name).map(Value::from_serialize)
+    }
+
+    fn fields(&self) -> Vec<Arc<str>>{
+        self.0.keys().map(|slug| Arc::from(slug.as_str())).collect()
+    }
+}
+
+/// Turns the Markdown of posts and pages into html, shortcodes included
+pub struct Renderer{
+    url: String,
+    site: Site,
+    posts: PostIndex,
+}
+
+impl Renderer{
+    pub fn new(url: &str, site: &Site, posts: &[Post]) -> Self{
+        let posts = posts.iter()
+            .map(|post| (post.slug.clone(), post.clone()))
+            .collect();
+        Self{
+            url: url.to_string(),
+            site: site.clone(),
+            posts: PostIndex(Arc::new(posts)),
+        }
+    }
+
+    pub fn render(&self, markdown: &str) -> String{
+        let (markdown, found) = shortcodes::extract(markdown);
+        let html = to_html(&markdown);
+        if found.is_empty(){
+            return html;
+        }
+        let rendered: Vec<String> = found.iter()
+            .map(|shortcode| self.render_shortcode(shortcode))
+            .collect();
+        shortcodes::restore(&html, &rendered)
+    }
+
+    /// Html of `templates/shortcodes/<name>.html`, or the shortcode as it
+    /// was written when it can not be rendered
+    fn render_shortcode(&self, shortcode: &Shortcode) -> String{
+        let name = format!("{}/{}.html", SHORTCODES_DIR, shortcode.name);
+        let template = match ENV.get_template(&name){
+            Ok(template) => template,
+            Err(err) => {
+                warn!("Unknown shortcode {}. {:#}", shortcode.name, err);
+                return source(shortcode);
+            }
+        };
+        let body = shortcode.body.as_ref()
+            .map(|body| Value::from_safe_string(self.render(body)));
+        let ctx = context! {
+            url => self.url,
+            site => self.site,
+            posts => Value::from_struct_object(self.posts.clone()),
+            args => shortcode.args,
+            kwargs => shortcode.kwargs,
+            body => body,
+        };
+        match template.render(ctx){
+            Ok(content) => content.trim().to_string(),
+            Err(err) => {
+                error!("Could not render shortcode {}: {:#}", shortcode.name, err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
+                }
+                source(shortcode)
+            }
+        }
+    }
+}
+
+pub fn to_html(markdown: &str) -> String{
+    let options = OPTIONS.get().cloned().unwrap_or_default();
+    to_html_with(markdown, &options, HIGHLIGHTER.as_ref())
+}
+
+fn to_html_with(markdown: &str, options: &Markdown, highlighter: Option<&SyntectAdapter>) -> String{
+    let mut plugins = Plugins::default();
+    if let Some(highlighter) = highlighter{
+        plugins.render.codefence_syntax_highlighter = Some(highlighter);
+    }
+    markdown_to_html_with_plugins(markdown, &options.get_options(), &plugins)
+}
+
+/// Headings of the rendered `html` with the ids comrak gave them, those in
+/// the body of shortcodes included. Empty without `header_ids`.
+pub fn toc(html: &str) -> Vec<Heading>{
+    HEADING.captures_iter(html)
+        .map(|caps| Heading{
+            level: caps[1].parse().unwrap_or(1),
+            title: html_escape::decode_html_entities(&TAGS.replace_all(&caps[3], "")).trim().to_string(),
+            id: html_escape::decode_html_entities(&caps[2]).to_string(),
+        })
+        .collect()
+}
+
+/// The shortcode as written, escaped
+fn source(shortcode: &Shortcode) -> String{
+    let mut parts = vec![shortcode.name.clone()];
+    parts.extend(shortcode.args.iter().cloned());
+    parts.extend(shortcode.kwargs.iter().map(|(key, value)| format!("{}=\"{}\"", key, value)));
+    html_escape::encode_text(&format!("{{{{< {} >}}}}", parts.join(" "))).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_html, to_html_with, toc, Markdown, Renderer, SyntectAdapter};
+    use crate::models::testing::{get_post, get_site};
+
+    #[test]
+    fn test_render(){
+        let renderer = Renderer::new("/podcast", &get_site(), &[get_post()]);
+        let html = renderer.render("Escuchad:\n\n{{< episode papa-friki-1 >}}\n\n{{< youtube abc123 >}}\n\n{{< callout >}}\n**Ojo**\n{{< /callout >}}\n\n{{< nope x >}}");
+        assert!(html.contains(r#"href="/podcast/papa-friki-1""#), "{}", html);
+        assert!(html.contains("https://archive.org/download/pf-1/pf-1.mp3"));
+        assert!(html.contains("https://www.youtube-nocookie.com/embed/abc123"), "{}", html);
+        assert!(html.contains("<strong>Ojo</strong>"));
+        assert!(html.contains("{{&lt; nope x &gt;}}"));
+        assert!(renderer.render("{{< episode no-existe >}}").contains("no-existe"));
+    }
+
+    fn get_options() -> Markdown{
+        serde_yaml::from_str("table: true\nstrikethrough: true\nheader_ids: true\ntoc: true\nhighlight: true").unwrap()
+    }
+
+    #[test]
+    fn test_to_html(){
+        let markdown = "## Instalación de `docker`\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n~~no~~\n\n```rust\nfn main(){}\n```\n";
+        let highlighter = SyntectAdapter::new(Some("InspiredGitHub"));
+        let html = to_html_with(markdown, &get_options(), Some(&highlighter));
+        assert!(html.contains(r#"id="instalación-de-docker""#), "{}", html);
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<del>no</del>"));
+        assert!(html.contains("<span style="));
+        // plain CommonMark unless configured
+        let html = to_html_with(markdown, &Markdown::default(), None);
+        assert!(html.contains("<h2>Instalación de <code>docker</code></h2>"), "{}", html);
+        assert!(!html.contains("<table>"));
+        assert!(!html.contains("<del>"));
+    }
+
+    #[test]
+    fn test_toc(){
+        let html = to_html_with("# Pi-hole\n\nTexto\n\n## Instalación de `docker` & <b>más</b>\n\n## Pi-hole", &get_options(), None);
+        // a heading rendered in the body of a shortcode
+        let html = format!("{}<div>{}</div>", html, to_html_with("### Notas", &get_options(), None));
+        let headings = toc(&html);
+        assert_eq!(headings.len(), 4);
+        assert_eq!(headings[1].level, 2);
+        assert_eq!(headings[1].title, "Instalación de docker & más");
+        assert_eq!(headings[1].id, "instalación-de-docker--más");
+        assert_eq!(headings[2].id, "pi-hole-1");
+        assert_eq!(headings[3].id, "notas");
+        for heading in &headings{
+            assert!(html.contains(&format!(r#"id="{}""#, heading.id)));
+        }
+        assert!(toc(&to_html("# Pi-hole")).is_empty());
+    }
+}
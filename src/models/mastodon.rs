@@ -1,22 +1,40 @@
-use reqwest::Client;
-use serde_json::json;
-use tracing::{info, error};
-
-pub fn get_mastodon_client() -> Option<Mastodon>{
-    match std::env::var("MASTODON_TOKEN"){
-        Ok(token) => {
-            match std::env::var("MASTODON_INSTANCE"){
-                Ok(instance) => Some(Mastodon::new(&token, &instance)),
-                Err(_) => None,
-            }
-        },
-        Err(_) => None,
+use async_trait::async_trait;
+use reqwest::{Client, multipart::{Form, Part}};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::{
+    announcer::{Announcer, AnnounceError, BoxError, render, get_image_url},
+    site::{Post, Site},
+};
+
+/// Options of the Mastodon channel. The token comes from `MASTODON_TOKEN`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MastodonConfig{
+    pub template: String,
+    // `MASTODON_INSTANCE` when missing
+    pub instance: Option<String>,
+    // public, unlisted, private or direct; the account default when missing
+    pub visibility: Option<String>,
+}
+
+impl Default for MastodonConfig{
+    fn default() -> Self{
+        Self{
+            template: "mastodon.html".to_string(),
+            instance: None,
+            visibility: None,
+        }
     }
 }
 
 pub struct Mastodon{
     instance: String,
     access_token: String,
+    template: String,
+    visibility: Option<String>,
 }
 
 impl Mastodon{
@@ -24,27 +42,89 @@ impl Mastodon{
         Mastodon {
             instance: instance.to_string(),
             access_token: access_token.to_string(),
+            template: MastodonConfig::default().template,
+            visibility: None,
         }
     }
 
-    pub async fn post(&self, message: &str){
+    pub fn from_config(config: &MastodonConfig) -> Option<Self>{
+        let token = std::env::var("MASTODON_TOKEN").ok()?;
+        let instance = match &config.instance{
+            Some(instance) => instance.to_owned(),
+            None => std::env::var("MASTODON_INSTANCE").ok()?,
+        };
+        Some(Self{
+            template: config.template.clone(),
+            visibility: config.visibility.clone(),
+            ..Self::new(&token, &instance)
+        })
+    }
+
+    /// Posts `message` with the picture at `image`, when given, attached
+    /// and returns the id of the status
+    pub async fn post(&self, message: &str, image: Option<&str>) -> Result<String, BoxError>{
         let url = format!("https://{}/api/v1/statuses", self.instance);
         info!("{}", &url);
-        let body = json!({"status": message});
-        match Client::new()
+        let mut body = json!({"status": message});
+        if let Some(visibility) = &self.visibility{
+            body["visibility"] = json!(visibility);
+        }
+        // a failed upload fails the status too, so the outbox retries both
+        if let Some(image) = image{
+            body["media_ids"] = json!([self.upload(image).await?]);
+        }
+        let status: Value = Client::new()
             .post(&url)
             .json(&body)
             .header("Authorization", format!("Bearer {}", self.access_token))
             .send()
-            .await{
-                Ok(response) => {
-                    info!("Mensaje envíado a Mastodon: {}",
-                        response.status().to_string());
-                },
-                Err(error) => {
-                    error!("No he podido enviar el mensaje a Mastodon: {}",
-                        error.to_string());
-                },
-            }
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        status["id"].as_str()
+            .map(|id| id.to_string())
+            .ok_or_else(|| format!("{} answered without the id of the status", url).into())
+    }
+
+    /// Uploads the picture at `image` and returns the id of the attachment
+    async fn upload(&self, image: &str) -> Result<String, BoxError>{
+        let client = Client::new();
+        let bytes = client.get(image)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let filename = image.rsplit('/').next().unwrap_or("image").to_string();
+        let form = Form::new()
+            .part("file", Part::bytes(bytes.to_vec()).file_name(filename));
+        let url = format!("https://{}/api/v2/media", self.instance);
+        let media: Value = client.post(&url)
+            .multipart(form)
+            .header("Authorization", format!("Bearer {}", self.access_token))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        media["id"].as_str()
+            .map(|id| id.to_string())
+            .ok_or_else(|| format!("{} answered without the id of the attachment", url).into())
+    }
+}
+
+#[async_trait]
+impl Announcer for Mastodon{
+    fn channel(&self) -> &str{
+        "mastodon"
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        let content = render(&self.template, site, post)?;
+        let image = get_image_url(site, post);
+        self.post(&content, image.as_deref())
+            .await
+            .map_err(|source| AnnounceError::Send{channel: self.channel().to_string(), source})
     }
 }
@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::{
+    announcer::{Announcer, AnnounceError, BoxError, render},
+    site::{Post, Site},
+    striptags,
+};
+
+/// Options of the Matrix channel. The access token comes from `MATRIX_TOKEN`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MatrixConfig{
+    pub template: String,
+    // `MATRIX_HOMESERVER` when missing
+    pub homeserver: Option<String>,
+    // `MATRIX_ROOM_ID` when missing
+    pub room_id: Option<String>,
+}
+
+impl Default for MatrixConfig{
+    fn default() -> Self{
+        Self{
+            template: "matrix.html".to_string(),
+            homeserver: None,
+            room_id: None,
+        }
+    }
+}
+
+pub struct Matrix{
+    homeserver: String,
+    room_id: String,
+    access_token: String,
+    template: String,
+}
+
+impl Matrix{
+    pub fn new(homeserver: &str, room_id: &str, access_token: &str) -> Self{
+        Self{
+            homeserver: homeserver.to_string(),
+            room_id: room_id.to_string(),
+            access_token: access_token.to_string(),
+            template: MatrixConfig::default().template,
+        }
+    }
+
+    pub fn from_config(config: &MatrixConfig) -> Option<Self>{
+        let token = std::env::var("MATRIX_TOKEN").ok()?;
+        let homeserver = match &config.homeserver{
+            Some(homeserver) => homeserver.to_owned(),
+            None => std::env::var("MATRIX_HOMESERVER").ok()?,
+        };
+        let room_id = match &config.room_id{
+            Some(room_id) => room_id.to_owned(),
+            None => std::env::var("MATRIX_ROOM_ID").ok()?,
+        };
+        Some(Self{
+            template: config.template.clone(),
+            ..Self::new(&homeserver, &room_id, &token)
+        })
+    }
+
+    /// Sends `html` to the room and returns the id of the event. The
+    /// homeserver ignores a second message with the same `transaction`.
+    pub async fn send(&self, html: &str, transaction: &str) -> Result<String, BoxError>{
+        let url = format!("{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            self.homeserver.trim_end_matches('/'), encode(&self.room_id), encode(transaction));
+        info!("{}", url);
+        let event: Value = Client::new()
+            .put(url)
+            .bearer_auth(&self.access_token)
+            .json(&json!({
+                "msgtype": "m.text",
+                "body": html_escape::decode_html_entities(&striptags(html.to_string())).trim(),
+                "format": "org.matrix.custom.html",
+                "formatted_body": html.trim(),
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        event["event_id"].as_str()
+            .map(|id| id.to_string())
+            .ok_or_else(|| "Matrix answered without the id of the event".into())
+    }
+}
+
+/// Percent-encodes a path segment, room ids look like `!id:server`
+fn encode(segment: &str) -> String{
+    segment.bytes()
+        .map(|byte| match byte{
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'!' | b':' | b'@' | b'$' => {
+                (byte as char).to_string()
+            },
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Announcer for Matrix{
+    fn channel(&self) -> &str{
+        "matrix"
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        let html = render(&self.template, site, post)?;
+        self.send(&html, &format!("aopodcast-{}", post.identifier))
+            .await
+            .map_err(|source| AnnounceError::Send{channel: self.channel().to_string(), source})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Matrix;
+    use crate::models::{announcer::Announcer, testing::{get_post, get_site}};
+    use serde_json::json;
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::{method, path, header, body_partial_json}};
+
+    #[tokio::test]
+    async fn test_announce(){
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/v3/rooms/!sala:matrix.org/send/m.room.message/aopodcast-pf-1"))
+            .and(header("Authorization", "Bearer secreto"))
+            .and(body_partial_json(json!({"msgtype": "m.text", "format": "org.matrix.custom.html"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"event_id": "$evento"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let matrix = Matrix::new(&server.uri(), "!sala:matrix.org", "secreto");
+        let mut post = get_post();
+        post.title = "Docker & Pi-hole".to_string();
+        let id = matrix.announce(&get_site(), &post).await.unwrap();
+        assert_eq!(id, "$evento");
+        let body: serde_json::Value = server.received_requests().await.unwrap()[0].body_json().unwrap();
+        assert!(body["body"].as_str().unwrap().contains("Docker & Pi-hole"), "{}", body["body"]);
+        assert!(body["formatted_body"].as_str().unwrap().contains("Docker &amp; Pi-hole"));
+        assert!(!body["body"].as_str().unwrap().contains("<a "));
+        assert!(body["formatted_body"].as_str().unwrap().contains("<a href="));
+    }
+
+    #[tokio::test]
+    async fn test_error(){
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+        let matrix = Matrix::new(&server.uri(), "!sala:matrix.org", "secreto");
+        assert!(matrix.announce(&get_site(), &get_post()).await.is_err());
+    }
+}
@@ -10,27 +10,60 @@ pub mod doc;
 pub mod utils;
 pub mod mastodon;
 pub mod telegram;
+pub mod assets;
+pub mod content;
+pub mod frontmatter;
+pub mod chapters;
+pub mod files;
+pub mod transcript;
+pub mod person;
+pub mod theme;
+pub mod shortcodes;
+pub mod markdown;
+pub mod search;
+pub mod related;
+pub mod data;
+pub mod stats;
+pub mod outbox;
+pub mod announcer;
+pub mod bluesky;
+pub mod matrix;
+pub mod discord;
+pub mod slack;
+pub mod email;
+pub mod webhook;
+#[cfg(test)]
+pub mod testing;
 
 pub use site::{Layout, Post};
 
-use minijinja::{Environment, path_loader};
+use minijinja::Environment;
 use once_cell::sync::Lazy;
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Locale, format::{Item, StrftimeItems}};
 use chrono_tz::Tz;
-use minijinja::value::{Kwargs, Value};
+use minijinja::value::{Kwargs, Value, ValueKind};
 use minijinja::{Error, ErrorKind, State};
+use std::fmt::Write;
+use tracing::warn;
 
 pub static ENV: Lazy<Environment<'static>> = Lazy::new(|| {
     let mut env = Environment::new();
-    env.set_loader(path_loader("templates"));
+    env.set_loader(theme::load);
     env.add_filter("striptags", striptags);
     env.add_filter("date", date);
     env.add_filter("truncate", truncate);
+    env.add_filter("duration", duration);
+    env.add_filter("filesizeformat", filesizeformat);
+    env.add_filter("slugify", slugify);
+    env.add_filter("markdown", markdown);
+    env.add_filter("absolute_url", absolute_url);
+    env.add_filter("reading_time", reading_time);
     env.add_function("now", now);
+    env.add_function("asset_url", asset_url);
     env
 });
 
-fn striptags(value: String) -> String {
+pub fn striptags(value: String) -> String {
     let mut data = String::new();
     let mut inside = false;
     // Step 1: loop over string chars.
@@ -52,54 +85,353 @@ fn striptags(value: String) -> String {
     data
 }
 
+/// Datetime of a RFC 3339 string, a `YYYY-MM-DD[ HH:MM[:SS]]` string or a
+/// Unix timestamp
 fn value_to_chrono_datetime(
     value: Value,
 ) -> Result<DateTime<FixedOffset>, Error> {
-    match value.as_str(){
-        Some(s) => match DateTime::parse_from_rfc3339(s){
-            Ok(dt) => Ok(dt),
-            Err(e) => Err(Error::new(
-                ErrorKind::MissingArgument,
-                e.to_string()
-            )),
-        },
-        None => Err(Error::new(
-            ErrorKind::MissingArgument,
-            "Not a valid string"
-        )),
+    if let Some(s) = value.as_str() {
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+            return Ok(datetime);
+        }
+        return utils::parse_datetime(s)
+            .map(|datetime| datetime.fixed_offset())
+            .ok_or_else(|| Error::new(
+                ErrorKind::InvalidOperation,
+                format!("`{}` is not a valid date", s)
+            ));
+    }
+    if value.kind() == ValueKind::Number {
+        let timestamp = f64::try_from(value.clone())?;
+        return DateTime::from_timestamp(
+            timestamp.trunc() as i64,
+            (timestamp.fract() * 1e9) as u32
+        )
+            .map(|datetime| datetime.fixed_offset())
+            .ok_or_else(|| Error::new(
+                ErrorKind::InvalidOperation,
+                format!("{} is not a valid timestamp", value)
+            ));
     }
+    Err(Error::new(
+        ErrorKind::InvalidOperation,
+        format!("can not format {} as a date", value.kind())
+    ))
 }
 
-pub fn date(_state: &State, value: Value, kwargs: Kwargs) -> Result<String, Error> {
-    let format = kwargs.get::<Option<&str>>("format")?;
-    match kwargs.get::<Option<&str>>("timezone")?{
-        Some(timezone) => {
-            let tz: Tz = timezone.parse().unwrap();
-            let datetime = value_to_chrono_datetime(value).unwrap().with_timezone(&tz);
-            Ok(format!("{}", datetime.format(format.unwrap())))
-        },
+/// Locale of a language tag as `es`, `es-ES` or `es_ES`. A bare language
+/// takes its main country.
+fn parse_locale(language: &str) -> Result<Locale, Error> {
+    let language = language.replace('-', "_");
+    let (lang, country) = match language.split_once('_') {
+        Some((lang, country)) => (lang.to_lowercase(), country.to_uppercase()),
         None => {
-            let datetime = value_to_chrono_datetime(value).unwrap();
-            Ok(format!("{}", datetime.format(format.unwrap())))
+            let lang = language.to_lowercase();
+            let country = match lang.as_str() {
+                "en" => "US".to_string(),
+                "ca" | "gl" | "eu" => "ES".to_string(),
+                _ => lang.to_uppercase(),
+            };
+            (lang, country)
+        },
+    };
+    Locale::try_from(format!("{}_{}", lang, country).as_str()).map_err(|_| Error::new(
+        ErrorKind::InvalidOperation,
+        format!("Unknown locale {}", language)
+    ))
+}
 
+/// Formats a date. Month and day names are in `locale`, or else in the
+/// language of the site.
+pub fn date(state: &State, value: Value, kwargs: Kwargs) -> Result<String, Error> {
+    let format = kwargs.get::<Option<&str>>("format")?.ok_or_else(|| Error::new(
+        ErrorKind::MissingArgument,
+        "date requires a format"
+    ))?;
+    let locale = match kwargs.get::<Option<&str>>("locale")? {
+        Some(locale) => parse_locale(locale)?,
+        None => match state.lookup("site")
+            .and_then(|site| site.get_attr("language").ok())
+            .and_then(|language| language.as_str().map(|language| language.to_string()))
+        {
+            Some(language) => parse_locale(&language)?,
+            None => Locale::POSIX,
         },
+    };
+    let timezone = kwargs.get::<Option<&str>>("timezone")?;
+    kwargs.assert_all_used()?;
+    let items: Vec<Item> = StrftimeItems::new_with_locale(format, locale).collect();
+    if items.contains(&Item::Error) {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid date format `{}`", format)
+        ));
     }
+    let datetime = value_to_chrono_datetime(value)?;
+    let datetime = match timezone {
+        Some(timezone) => {
+            let tz: Tz = timezone.parse().map_err(|_| Error::new(
+                ErrorKind::InvalidOperation,
+                format!("unknown timezone {}", timezone)
+            ))?;
+            datetime.with_timezone(&tz).fixed_offset()
+        },
+        None => datetime,
+    };
+    let mut formatted = String::new();
+    write!(formatted, "{}", datetime.format_localized_with_items(items.iter(), locale))
+        .map_err(|_| Error::new(
+            ErrorKind::InvalidOperation,
+            format!("can not format date with `{}`", format)
+        ))?;
+    Ok(formatted)
 }
 
+/// Cuts a text to `length` characters, `end` included. With
+/// `killwords=false` it stops at the last whole word.
 pub fn truncate(_state: &State, value: Value, kwargs: Kwargs) -> Result<String, Error> {
-    let length = kwargs.get::<Option<usize>>("length")?.unwrap();
-    match value.as_str() {
-        Some(s) => match value.as_str().unwrap().char_indices().nth(length) {
-            None => Ok(s.to_string()),
-            Some((idx, _)) => Ok(s[..idx].to_string()),
+    let length = kwargs.get::<Option<usize>>("length")?.ok_or_else(|| Error::new(
+        ErrorKind::MissingArgument,
+        "truncate requires a length"
+    ))?;
+    let killwords = kwargs.get::<Option<bool>>("killwords")?.unwrap_or(true);
+    let end = kwargs.get::<Option<&str>>("end")?.unwrap_or("");
+    kwargs.assert_all_used()?;
+    let s = value.as_str().ok_or_else(|| Error::new(
+        ErrorKind::InvalidOperation,
+        format!("can not truncate {}", value.kind())
+    ))?;
+    if s.chars().count() <= length {
+        return Ok(s.to_string());
+    }
+    let keep = length.saturating_sub(end.chars().count());
+    let idx = s.char_indices().nth(keep).map(|(idx, _)| idx).unwrap_or(s.len());
+    let mut truncated = &s[..idx];
+    // a cut right before a space already ends in a whole word
+    let at_boundary = s[idx..].starts_with(char::is_whitespace);
+    if !killwords && !at_boundary {
+        if let Some(space) = truncated.rfind(char::is_whitespace) {
+            truncated = truncated[..space].trim_end();
+        }
+    }
+    Ok(format!("{}{}", truncated, end))
+}
+
+/// Seconds as `1:02:15`, or `1 h 2 min` with `format="human"`
+pub fn duration(seconds: u64, kwargs: Kwargs) -> Result<String, Error> {
+    let format = kwargs.get::<Option<&str>>("format")?.unwrap_or("hh:mm:ss");
+    kwargs.assert_all_used()?;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match format {
+        "hh:mm:ss" => if hours > 0 {
+            Ok(format!("{}:{:02}:{:02}", hours, minutes, seconds))
+        } else {
+            Ok(format!("{}:{:02}", minutes, seconds))
+        },
+        "human" => {
+            let mut parts = Vec::new();
+            if hours > 0 {
+                parts.push(format!("{} h", hours));
+            }
+            if minutes > 0 {
+                parts.push(format!("{} min", minutes));
+            }
+            if parts.is_empty() {
+                parts.push(format!("{} s", seconds));
+            }
+            Ok(parts.join(" "))
         },
-        None => Err(Error::new(
-            ErrorKind::MissingArgument,
-            "Not a valid string"
+        _ => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("Unknown duration format {}", format)
         )),
     }
 }
 
+/// Bytes as `54.3 MB`, or `51.8 MiB` with `binary=true`
+pub fn filesizeformat(bytes: f64, kwargs: Kwargs) -> Result<String, Error> {
+    let binary = kwargs.get::<Option<bool>>("binary")?.unwrap_or(false);
+    kwargs.assert_all_used()?;
+    let (base, units) = if binary {
+        (1024.0, ["KiB", "MiB", "GiB", "TiB"])
+    } else {
+        (1000.0, ["kB", "MB", "GB", "TB"])
+    };
+    if bytes < base {
+        return Ok(format!("{} {}", bytes, if bytes == 1.0 { "Byte" } else { "Bytes" }));
+    }
+    let mut size = bytes / base;
+    for unit in &units[..units.len() - 1] {
+        if size < base {
+            return Ok(format!("{:.1} {}", size, unit));
+        }
+        size /= base;
+    }
+    Ok(format!("{:.1} {}", size, units[units.len() - 1]))
+}
+
+pub fn slugify(value: &str) -> String {
+    utils::get_slug(value)
+}
+
+pub fn markdown(value: &str) -> Value {
+    Value::from_safe_string(markdown::to_html(value))
+}
+
+/// `site.url` plus `path`, relative to `url` unless it starts with `/`.
+/// Urls with a scheme are left alone.
+pub fn absolute_url(state: &State, path: &str) -> String {
+    if path.contains("://") {
+        return path.to_string();
+    }
+    let site_url = state.lookup("site")
+        .and_then(|site| site.get_attr("url").ok())
+        .and_then(|url| url.as_str().map(|url| url.trim_end_matches('/').to_string()))
+        .unwrap_or_default();
+    if path.starts_with('/') {
+        return format!("{}{}", site_url, path);
+    }
+    let url = state.lookup("url")
+        .and_then(|url| url.as_str().map(|url| url.to_string()))
+        .unwrap_or_default();
+    format!("{}{}/{}", site_url, url, path)
+}
+
+/// Minutes it takes to read an html or plain text, at least one
+pub fn reading_time(value: String, kwargs: Kwargs) -> Result<usize, Error> {
+    let words_per_minute = kwargs.get::<Option<usize>>("words_per_minute")?.unwrap_or(200);
+    kwargs.assert_all_used()?;
+    if words_per_minute == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "words_per_minute must be greater than zero"
+        ));
+    }
+    let words = striptags(value).split_whitespace().count();
+    Ok(words.div_ceil(words_per_minute).max(1))
+}
+
+pub fn asset_url(state: &State, path: &str) -> String {
+    let url = state.lookup("url")
+        .and_then(|url| url.as_str().map(|url| url.to_string()))
+        .unwrap_or_default();
+    let path = path.trim_start_matches('/');
+    let published = match assets::MANIFEST.read().unwrap().get(path) {
+        Some(published) => published.to_string(),
+        None => {
+            warn!("Asset {} not found", path);
+            path.to_string()
+        }
+    };
+    format!("{}/assets/{}", url, published)
+}
+
 pub fn now() -> String {
     chrono::Utc::now().to_rfc3339()
 }
+
+#[cfg(test)]
+mod tests {
+    use minijinja::{context, Environment};
+
+    fn render(template: &str) -> String {
+        try_render(template).unwrap()
+    }
+
+    fn try_render(template: &str) -> Result<String, minijinja::Error> {
+        let mut env = Environment::new();
+        env.add_filter("date", super::date);
+        env.add_filter("truncate", super::truncate);
+        env.add_filter("duration", super::duration);
+        env.add_filter("filesizeformat", super::filesizeformat);
+        env.add_filter("slugify", super::slugify);
+        env.add_filter("markdown", super::markdown);
+        env.add_filter("absolute_url", super::absolute_url);
+        env.add_filter("reading_time", super::reading_time);
+        let ctx = context! {
+            url => "/podcast",
+            site => context! { url => "https://atareao.es", language => "es" },
+            date => "2020-05-15T10:00:00+00:00",
+            text => "palabra ".repeat(450),
+        };
+        env.render_str(template, ctx)
+    }
+
+    #[test]
+    fn test_date(){
+        assert_eq!(render("{{ date | date(format='%A %d de %B') }}"), "viernes 15 de mayo");
+        assert_eq!(render("{{ date | date(format='%a, %d %b %Y', locale='en') }}"), "Fri, 15 May 2020");
+        assert_eq!(render("{{ date | date(format='%B', locale='pt-BR') }}"), "maio");
+        assert_eq!(render("{{ date | date(format='%d/%m/%Y') }}"), "15/05/2020");
+        assert_eq!(render("{{ date | date(format='%H:%M', timezone='Europe/Madrid') }}"), "12:00");
+        assert_eq!(render("{{ '2020-05-15' | date(format='%d/%m/%Y') }}"), "15/05/2020");
+        assert_eq!(render("{{ 1589536800 | date(format='%d/%m/%Y %H:%M') }}"), "15/05/2020 10:00");
+    }
+
+    #[test]
+    fn test_date_errors(){
+        for template in [
+            "{{ date | date }}",
+            "{{ date | date(format='%Q') }}",
+            "{{ date | date(format='%d', timezone='Europe/Madird') }}",
+            "{{ date | date(format='%d', locale='xx') }}",
+            "{{ date | date(fromat='%d') }}",
+            "{{ 'ayer' | date(format='%d') }}",
+        ]{
+            let err = try_render(template).unwrap_err();
+            assert_eq!(err.line(), Some(1), "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_truncate(){
+        assert_eq!(render("{{ 'Papá Friki en Docker' | truncate(length=10) }}"), "Papá Friki");
+        assert_eq!(render("{{ 'Papá Friki en Docker' | truncate(length=12, killwords=false) }}"), "Papá Friki");
+        assert_eq!(render("{{ 'Papá Friki en Docker' | truncate(length=10, killwords=false) }}"), "Papá Friki");
+        assert_eq!(render("{{ 'Papá Friki en Docker' | truncate(length=9, killwords=false) }}"), "Papá");
+        assert_eq!(render("{{ 'Papá Friki en Docker' | truncate(length=14, killwords=false, end='...') }}"), "Papá Friki...");
+        assert_eq!(render("{{ 'Papá' | truncate(length=10, end='...') }}"), "Papá");
+        assert!(try_render("{{ 'Papá' | truncate }}").is_err());
+        assert!(try_render("{{ 10 | truncate(length=1) }}").is_err());
+    }
+
+    #[test]
+    fn test_duration(){
+        assert_eq!(render("{{ 3735 | duration }}"), "1:02:15");
+        assert_eq!(render("{{ 750 | duration }}"), "12:30");
+        assert_eq!(render("{{ 3735 | duration(format='human') }}"), "1 h 2 min");
+        assert_eq!(render("{{ 45 | duration(format='human') }}"), "45 s");
+    }
+
+    #[test]
+    fn test_filesizeformat(){
+        assert_eq!(render("{{ 54300000 | filesizeformat }}"), "54.3 MB");
+        assert_eq!(render("{{ 54300000 | filesizeformat(binary=true) }}"), "51.8 MiB");
+        assert_eq!(render("{{ 1 | filesizeformat }}"), "1 Byte");
+        assert_eq!(render("{{ 999 | filesizeformat }}"), "999 Bytes");
+    }
+
+    #[test]
+    fn test_slugify(){
+        assert_eq!(render("{{ 'Papá Friki 1' | slugify }}"), "papa-friki-1");
+    }
+
+    #[test]
+    fn test_markdown(){
+        assert_eq!(render("{{ '**Pi-hole**' | markdown }}"), "<p><strong>Pi-hole</strong></p>\n");
+    }
+
+    #[test]
+    fn test_absolute_url(){
+        assert_eq!(render("{{ '/assets/cover.jpg' | absolute_url }}"), "https://atareao.es/assets/cover.jpg");
+        assert_eq!(render("{{ 'feed' | absolute_url }}"), "https://atareao.es/podcast/feed");
+        assert_eq!(render("{{ 'https://archive.org/a.jpg' | absolute_url }}"), "https://archive.org/a.jpg");
+    }
+
+    #[test]
+    fn test_reading_time(){
+        assert_eq!(render("{{ '<p>Hola</p>' | reading_time }}"), "1");
+        assert_eq!(render("{{ text | reading_time }}"), "3");
+        assert_eq!(render("{{ text | reading_time(words_per_minute=100) }}"), "5");
+    }
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+use tracing::{debug, warn};
+
+/// Attempts before an announcement is given up
+pub const MAX_ATTEMPTS: u32 = 10;
+const FIRST_RETRY_MINUTES: i64 = 5;
+const MAX_RETRY_HOURS: i64 = 24;
+
+/// Announcement of an episode waiting to be sent to a channel
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Announcement{
+    pub identifier: String,
+    pub channel: String,
+    #[serde(default)]
+    pub attempts: u32,
+    pub next_attempt: DateTime<Utc>,
+}
+
+/// Pending announcements, kept in the data store between runs
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Outbox(Vec<Announcement>);
+
+impl Outbox{
+    /// Queues the announcement of `identifier` in `channel` unless it is
+    /// already waiting. Returns whether it was added.
+    pub fn enqueue(&mut self, identifier: &str, channel: &str, now: DateTime<Utc>) -> bool{
+        if self.0.iter().any(|item| item.identifier == identifier && item.channel == channel){
+            return false;
+        }
+        debug!("Announcement of {} in {} queued", identifier, channel);
+        self.0.push(Announcement{
+            identifier: identifier.to_string(),
+            channel: channel.to_string(),
+            attempts: 0,
+            next_attempt: now,
+        });
+        true
+    }
+
+    /// Announcements whose time has come, oldest first
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<Announcement>{
+        self.0.iter()
+            .filter(|item| item.next_attempt <= now)
+            .cloned()
+            .collect()
+    }
+
+    pub fn remove(&mut self, announcement: &Announcement){
+        self.0.retain(|item| {
+            item.identifier != announcement.identifier || item.channel != announcement.channel
+        });
+    }
+
+    /// Schedules the next attempt, further away every time, or drops the
+    /// announcement after `MAX_ATTEMPTS`. Returns whether it will be retried.
+    pub fn fail(&mut self, announcement: &Announcement, now: DateTime<Utc>) -> bool{
+        let item = match self.0.iter_mut().find(|item| {
+            item.identifier == announcement.identifier && item.channel == announcement.channel
+        }){
+            Some(item) => item,
+            None => return false,
+        };
+        item.attempts += 1;
+        if item.attempts >= MAX_ATTEMPTS{
+            warn!("Giving up announcing {} in {} after {} attempts",
+                item.identifier, item.channel, item.attempts);
+            self.remove(announcement);
+            return false;
+        }
+        item.next_attempt = now + backoff(item.attempts);
+        true
+    }
+}
+
+fn backoff(attempts: u32) -> Duration{
+    let minutes = FIRST_RETRY_MINUTES.saturating_mul(1 << attempts.saturating_sub(1).min(20));
+    Duration::minutes(minutes).min(Duration::hours(MAX_RETRY_HOURS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff, Outbox, MAX_ATTEMPTS};
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn test_backoff(){
+        assert_eq!(backoff(1), Duration::minutes(5));
+        assert_eq!(backoff(3), Duration::minutes(20));
+        assert_eq!(backoff(30), Duration::hours(24));
+    }
+
+    #[test]
+    fn test_outbox(){
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 10, 0, 0).unwrap();
+        let mut outbox = Outbox::default();
+        assert!(outbox.enqueue("pf-1", "telegram", now));
+        assert!(outbox.enqueue("pf-1", "mastodon", now));
+        assert!(!outbox.enqueue("pf-1", "telegram", now));
+        let due = outbox.due(now);
+        assert_eq!(due.len(), 2);
+        assert!(outbox.fail(&due[0], now));
+        assert_eq!(outbox.due(now).len(), 1);
+        assert_eq!(outbox.due(now + Duration::minutes(5)).len(), 2);
+        outbox.remove(&due[1]);
+        assert_eq!(outbox.due(now + Duration::days(1)).len(), 1);
+        for _ in 1..MAX_ATTEMPTS - 1{
+            assert!(outbox.fail(&due[0], now));
+        }
+        assert!(!outbox.fail(&due[0], now));
+        assert!(outbox.due(now + Duration::days(1)).is_empty());
+    }
+}
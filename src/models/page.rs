@@ -1,119 +1,27 @@
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info, error};
-use gray_matter::{Matter, engine::YAML};
-use comrak::{markdown_to_html, ComrakOptions};
 
 use super::{
-    site::{Post, Layout},
-    utils::{get_slug,
-            get_unix_time,
-            get_excerpt,
-    },
+    site::Layout,
+    content::{Common, Schema},
 };
 
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Metadata{
-    pub title: String,
-    pub date: String,
-    pub excerpt: String,
-    pub slug: String,
-    pub version: usize,
+pub struct Metadata{
+    #[serde(flatten)]
+    pub common: Common,
 }
 
-impl Metadata{
-    pub fn get_filename(&self) -> String {
-        format!("pages/{}.md", self.slug)
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Page{
-    metadata: Metadata,
-    pub content: String,
-}
+impl Schema for Metadata{
+    const DIR: &'static str = "pages";
+    const LAYOUT: Layout = Layout::PAGE;
 
-impl Page{
-    pub fn get_post(&self) -> Post{
-        let date = get_unix_time(&self.metadata.date);
-        let content = markdown_to_html(&self.content, &ComrakOptions::default());
-        Post{
-            title: self.metadata.title.clone(),
-            date,
-            version: self.metadata.version,
-            excerpt: self.metadata.excerpt.clone(),
-            layout: Layout::PAGE,
-            slug: self.metadata.slug.clone(),
-            content,
-            subject: Vec::new(),
-            identifier: self.metadata.slug.clone(),
-            filename: "".to_string(),
-            size: 0,
-            length: 0,
-            number: 0,
-            downloads: 0,
-        }
+    fn common(&self) -> &Common{
+        &self.common
     }
 
-    pub async fn new(filename: &str) -> Result<Self, serde_json::Error>{
-        let mut save = false;
-        let filename = format!("pages/{}", filename);
-        debug!("Filename: {}", filename);
-        let data = tokio::fs::read_to_string(&filename)
-            .await
-            .unwrap();
-        let matter = Matter::<YAML>::new();
-        let result = matter.parse(&data);
-        let mut metadata: Metadata = result.data.unwrap().deserialize()?;
-        debug!("Metadata: {:?}", &metadata);
-        if metadata.slug.is_empty(){
-            debug!("Is empty");
-            metadata.slug = get_slug(&metadata.title);
-            save = true;
-        }
-        if metadata.excerpt.is_empty(){
-            metadata.excerpt = match result.excerpt {
-                Some(excerpt) => {
-                    save = true;
-                    excerpt
-                },
-                None => get_excerpt(&result.content).to_string(),
-            };
-        }
-        debug!("Metadata: {:?}", &metadata);
-        let page = Page{
-            metadata,
-            content: result.content,
-        };
-        if save{
-            match page.save().await{
-                Ok(_) => {
-                    info!("Saved page {}", page.get_filename());
-                    if filename != page.get_filename(){
-                        match tokio::fs::remove_file(&filename).await{
-                            Ok(_) => info!("Removed {}", &filename),
-                            Err(e) => error!("Cant remove {}. {}", &filename, e),
-                        }
-                    }
-                },
-                Err(_) => error!("Cant save page {}", page.get_filename()),
-            }
-        }
-        Ok(page)
-    }
-
-    pub fn get_filename(&self) -> String{
-        self.metadata.get_filename()
-    }
-
-    pub async fn save(&self)-> tokio::io::Result<()>{
-        let mut content = String::new();
-        content.push_str("---\n");
-        content.push_str(&serde_yaml::to_string(&self.metadata).unwrap());
-        content.push_str("---\n");
-        content.push_str(&self.content);
-        debug!("Content: {}", content);
-        tokio::fs::write(self.get_filename(), content).await
+    fn common_mut(&mut self) -> &mut Common{
+        &mut self.common
     }
 }
 
@@ -126,21 +34,20 @@ mod tests {
     };
     use std::str::FromStr;
     use tracing::debug;
-    use crate::models::page::Page;
+    use crate::models::{content::Document, page::Metadata};
 
     #[tokio::test]
     async fn test_page(){
-        tracing_subscriber::registry()
+        let _ = tracing_subscriber::registry()
             .with(EnvFilter::from_str("debug").unwrap())
             .with(tracing_subscriber::fmt::layer())
-            .init();
+            .try_init();
 
-        let page = Page::new("about.md").await.unwrap();
-        debug!("Title: {}", page.metadata.title);
+        let page = Document::<Metadata>::new("acerca-de.md").await.unwrap();
+        debug!("Title: {}", page.metadata.common.title);
         debug!("=========================");
         debug!("{:?}", page);
         debug!("=========================");
-        assert!(!page.metadata.title.is_empty());
+        assert!(!page.metadata.common.title.is_empty());
     }
 }
-
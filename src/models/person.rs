@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+use super::{
+    site::Layout,
+    markdown::to_html,
+    content::{Common, Document, Schema},
+    utils::get_excerpt,
+};
+
+const DEFAULT_ROLE: &str = "guest";
+
+/// Front matter of `people/<slug>.md`. The body is the bio and the name of
+/// the file the slug, unless the front matter has one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Metadata{
+    #[serde(flatten)]
+    pub common: Common,
+    #[serde(default)]
+    pub avatar: String,
+    #[serde(default)]
+    pub url: String,
+    // name of the link => url
+    #[serde(default)]
+    pub links: BTreeMap<String, String>,
+}
+
+impl Schema for Metadata{
+    const DIR: &'static str = "people";
+    const LAYOUT: Layout = Layout::PERSON;
+    const ALIASES: &'static [(&'static str, &'static str)] = &[("name", "title")];
+
+    fn common(&self) -> &Common{
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut Common{
+        &mut self.common
+    }
+
+    fn default_slug(&self, path: &str) -> String{
+        Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Entry of the `people` section of the configuration, for those without
+/// a file in `people/`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PersonConfig{
+    pub slug: String,
+    pub name: String,
+    // Markdown
+    #[serde(default)]
+    pub bio: String,
+    #[serde(default)]
+    pub avatar: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub links: BTreeMap<String, String>,
+}
+
+impl PersonConfig{
+    pub fn get_person(&self) -> Person{
+        Person{
+            slug: self.slug.clone(),
+            name: self.name.clone(),
+            excerpt: get_excerpt(&self.bio).to_string(),
+            bio: to_html(&self.bio),
+            avatar: self.avatar.clone(),
+            url: self.url.clone(),
+            links: self.links.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Person{
+    pub slug: String,
+    pub name: String,
+    pub excerpt: String,
+    pub bio: String,
+    pub avatar: String,
+    pub url: String,
+    pub links: BTreeMap<String, String>,
+}
+
+impl Document<Metadata>{
+    pub fn get_person(&self) -> Person{
+        Person{
+            slug: self.metadata.common.slug.clone(),
+            name: self.metadata.common.title.clone(),
+            excerpt: self.metadata.common.excerpt.clone(),
+            bio: to_html(&self.content),
+            avatar: self.metadata.avatar.clone(),
+            url: self.metadata.url.clone(),
+            links: self.metadata.links.clone(),
+        }
+    }
+}
+
+/// Someone taking part in an episode. In front matter either just the slug
+/// of the person or `{slug, role}`; the rest is filled in from `people/`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "CreditSource")]
+pub struct Credit{
+    pub slug: String,
+    pub role: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub avatar: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CreditSource{
+    Slug(String),
+    Full{
+        slug: String,
+        #[serde(default = "default_role")]
+        role: String,
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        avatar: String,
+    },
+}
+
+fn default_role() -> String{
+    DEFAULT_ROLE.to_string()
+}
+
+impl From<CreditSource> for Credit{
+    fn from(source: CreditSource) -> Self{
+        match source{
+            CreditSource::Slug(slug) => Credit{
+                name: slug.clone(),
+                slug,
+                role: default_role(),
+                avatar: "".to_string(),
+            },
+            CreditSource::Full{slug, role, name, avatar} => Credit{
+                name: if name.is_empty(){ slug.clone() }else{ name },
+                slug,
+                role: role.to_lowercase(),
+                avatar,
+            },
+        }
+    }
+}
+
+impl Credit{
+    pub fn host(person: &Person) -> Self{
+        let mut credit = Credit::from(CreditSource::Slug(person.slug.clone()));
+        credit.role = "host".to_string();
+        credit.fill(person);
+        credit
+    }
+
+    pub fn fill(&mut self, person: &Person){
+        self.name = person.name.clone();
+        self.avatar = person.avatar.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Credit, PersonConfig};
+    use crate::models::{content::Document, person::Metadata};
+
+    #[test]
+    fn test_credits(){
+        let credits: Vec<Credit> = serde_yaml::from_str("- lorenzo\n- slug: alberto\n  role: Host").unwrap();
+        assert_eq!(credits[0].role, "guest");
+        assert_eq!(credits[0].name, "lorenzo");
+        assert_eq!(credits[1].role, "host");
+    }
+
+    #[test]
+    fn test_person(){
+        let data = "---\nname: Lorenzo Carbonell\navatar: https://atareao.es/avatar.png\nlinks:\n  mastodon: https://mastodon.social/@atareao\n---\nCreador de **atareao**";
+        let (person, _) = Document::<Metadata>::parse("people/atareao.md", data).unwrap();
+        let person = person.get_person();
+        assert_eq!(person.slug, "atareao");
+        assert_eq!(person.name, "Lorenzo Carbonell");
+        assert_eq!(person.links["mastodon"], "https://mastodon.social/@atareao");
+        assert!(person.bio.contains("<strong>atareao</strong>"));
+    }
+
+    #[test]
+    fn test_person_config(){
+        let config: PersonConfig = serde_yaml::from_str("slug: alberto\nname: Papá Friki\nbio: Padre y **friki**").unwrap();
+        let person = config.get_person();
+        assert_eq!(person.slug, "alberto");
+        assert_eq!(person.excerpt, "Padre y **friki**");
+        assert!(person.bio.contains("<strong>friki</strong>"));
+        assert!(person.links.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+
+use super::site::{Layout, Post};
+
+/// Related posts shown with every post
+pub const RELATED: usize = 3;
+/// Shorter words are mostly articles and prepositions
+const MIN_WORD_LENGTH: usize = 4;
+/// Weight of the text similarity next to a shared tag, that counts one
+const TEXT_WEIGHT: f64 = 2.0;
+
+/// What a template needs to link to another post
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PostSummary{
+    pub layout: Layout,
+    pub slug: String,
+    pub title: String,
+    pub excerpt: String,
+    pub date: DateTime<Utc>,
+    pub image: Option<String>,
+}
+
+impl From<&Post> for PostSummary{
+    fn from(post: &Post) -> Self{
+        Self{
+            layout: post.layout,
+            slug: post.slug.clone(),
+            title: post.title.clone(),
+            excerpt: post.excerpt.clone(),
+            date: post.date,
+            image: post.image.clone(),
+        }
+    }
+}
+
+/// Sets `related`, `previous` and `next` of every post. Previous and next are
+/// the older and newer posts of the same layout.
+pub fn link(posts: &mut [Post]){
+    let words: Vec<HashSet<String>> = posts.iter().map(get_words).collect();
+    let summaries: Vec<PostSummary> = posts.iter().map(PostSummary::from).collect();
+    let mut order: Vec<usize> = (0..posts.len()).collect();
+    order.sort_by_key(|&index| posts[index].date);
+    for index in 0..posts.len(){
+        let mut scores: Vec<(f64, usize)> = (0..posts.len())
+            .filter(|&other| other != index)
+            .map(|other| {
+                let tags = posts[index].subject.iter()
+                    .filter(|tag| posts[other].subject.contains(tag))
+                    .count() as f64;
+                (tags + TEXT_WEIGHT * jaccard(&words[index], &words[other]), other)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        scores.sort_by(|a, b| {
+            b.0.total_cmp(&a.0).then(posts[b.1].date.cmp(&posts[a.1].date))
+        });
+        posts[index].related = scores.iter()
+            .take(RELATED)
+            .map(|(_, other)| summaries[*other].clone())
+            .collect();
+    }
+    for (position, &index) in order.iter().enumerate(){
+        let layout = posts[index].layout;
+        posts[index].previous = order[..position].iter().rev()
+            .find(|&&other| posts[other].layout == layout)
+            .map(|&other| summaries[other].clone());
+        posts[index].next = order[position + 1..].iter()
+            .find(|&&other| posts[other].layout == layout)
+            .map(|&other| summaries[other].clone());
+    }
+}
+
+/// Lowercase words of the title and excerpt
+fn get_words(post: &Post) -> HashSet<String>{
+    format!("{} {}", post.title, post.excerpt)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH)
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64{
+    let union = a.union(b).count();
+    if union == 0{
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::link;
+    use crate::models::{article, testing::{get_episode, parse}, Post};
+
+    fn episode(number: usize, title: &str, subject: &str) -> Post{
+        get_episode(number, &[
+            ("title", title),
+            ("subject", &format!("[{}]", subject)),
+            ("date", &format!("2024-05-{:02}", number)),
+            ("excerpt", title),
+        ])
+    }
+
+    #[test]
+    fn test_link(){
+        let data = "---\ntitle: Montando WireGuard en casa\ndate: 2024-05-03\nsubject: [vpn]\n---\nTexto";
+        let article = parse::<article::Metadata>(data);
+        let mut posts = vec![
+            episode(1, "Servidor WireGuard", "linux, vpn"),
+            episode(2, "Pi-hole con Docker", "docker"),
+            episode(4, "Docker en la Raspberry", "docker, linux"),
+            article,
+        ];
+        link(&mut posts);
+        assert_eq!(posts[0].related[0].slug, "montando-wireguard-en-casa");
+        assert_eq!(posts[0].related[1].slug, "docker-en-la-raspberry");
+        assert_eq!(posts[1].related.len(), 1);
+        assert_eq!(posts[1].previous.as_ref().unwrap().slug, "servidor-wireguard");
+        assert_eq!(posts[1].next.as_ref().unwrap().slug, "docker-en-la-raspberry");
+        assert!(posts[0].previous.is_none());
+        // articles and episodes are not mixed
+        assert!(posts[3].previous.is_none() && posts[3].next.is_none());
+    }
+}
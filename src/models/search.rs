@@ -0,0 +1,147 @@
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeSet;
+
+use super::{site::Post, striptags};
+
+/// Words shorter than this are left out of the stemmed terms
+const MIN_TERM_LENGTH: usize = 2;
+
+/// The `search` section of the configuration
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Search{
+    pub enabled: bool,
+    // publish the stems of the words instead of the whole text
+    pub stem: bool,
+}
+
+impl Default for Search{
+    fn default() -> Self{
+        Self{
+            enabled: true,
+            stem: false,
+        }
+    }
+}
+
+/// Entry of `search.json`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Entry{
+    pub slug: String,
+    pub title: String,
+    pub excerpt: String,
+    pub tags: Vec<String>,
+    pub date: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub terms: Vec<String>,
+}
+
+impl Search{
+    /// Index of `posts` in the order given, stemmed for `language` when
+    /// enabled and there is a stemmer for it
+    pub fn build(&self, posts: &[Post], language: &str) -> Vec<Entry>{
+        let stemmer = if self.stem{
+            get_algorithm(language).map(Stemmer::create)
+        }else{
+            None
+        };
+        posts.iter()
+            .map(|post| {
+                let text = get_text(&post.content);
+                let (content, terms) = match &stemmer{
+                    Some(stemmer) => ("".to_string(), get_terms(stemmer, &text)),
+                    None => (text, Vec::new()),
+                };
+                Entry{
+                    slug: post.slug.clone(),
+                    title: post.title.clone(),
+                    excerpt: striptags(post.excerpt.clone()),
+                    tags: post.subject.clone(),
+                    date: post.date.format("%Y-%m-%d").to_string(),
+                    content,
+                    terms,
+                }
+            })
+            .collect()
+    }
+}
+
+fn get_algorithm(language: &str) -> Option<Algorithm>{
+    let language = language.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    match language.as_str(){
+        "es" => Some(Algorithm::Spanish),
+        "en" => Some(Algorithm::English),
+        "pt" => Some(Algorithm::Portuguese),
+        "fr" => Some(Algorithm::French),
+        "it" => Some(Algorithm::Italian),
+        "de" => Some(Algorithm::German),
+        _ => None,
+    }
+}
+
+/// Text of an html content with the whitespace collapsed
+fn get_text(html: &str) -> String{
+    let text = html_escape::decode_html_entities(&striptags(html.to_string())).to_string();
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Unique stems of the words of `text`, without accents as the search page
+/// compares them
+fn get_terms(stemmer: &Stemmer, text: &str) -> Vec<String>{
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_TERM_LENGTH)
+        .map(|word| fold(&stemmer.stem(&word.to_lowercase())))
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect()
+}
+
+fn fold(word: &str) -> String{
+    word.chars()
+        .map(|c| match c{
+            'á' | 'à' | 'â' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Search;
+    use crate::models::{article, testing::parse, Post};
+
+    fn get_post() -> Post{
+        parse::<article::Metadata>("---\ntitle: Túnel con WireGuard\ndate: 2024-05-15\nsubject: [wireguard, vpn]\nexcerpt: Cómo montar <b>WireGuard</b>\n---\n## Instalación\n\nConfiguramos los túneles &amp; las claves.")
+    }
+
+    #[test]
+    fn test_index(){
+        let index = Search::default().build(&[get_post()], "es");
+        assert_eq!(index[0].slug, "tunel-con-wireguard");
+        assert_eq!(index[0].excerpt, "Cómo montar WireGuard");
+        assert_eq!(index[0].date, "2024-05-15");
+        assert_eq!(index[0].tags, vec!["wireguard", "vpn"]);
+        assert_eq!(index[0].content, "Instalación Configuramos los túneles & las claves.");
+        assert!(index[0].terms.is_empty());
+    }
+
+    #[test]
+    fn test_stemmed(){
+        let search = Search{ enabled: true, stem: true };
+        let index = search.build(&[get_post()], "es-ES");
+        assert!(index[0].content.is_empty());
+        assert!(index[0].terms.contains(&"tunel".to_string()), "{:?}", index[0].terms);
+        assert!(index[0].terms.contains(&"configur".to_string()), "{:?}", index[0].terms);
+        // no stemmer, whole text
+        assert!(!search.build(&[get_post()], "eo")[0].content.is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use regex::Regex;
+use std::{collections::BTreeMap, ops::Range};
+
+/// `{{< name arg key="value" >}}`, or `{{< /name >}}` closing a paired one
+const TAG: &str = r#"\{\{<\s*(/?)([\w-]+)((?:\s+(?:[\w-]+=)?(?:"[^"]*"|[^\s">]+))*)\s*>\}\}"#;
+const ARG: &str = r#"(?:([\w-]+)=)?(?:"([^"]*)"|([^\s"]+))"#;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Shortcode{
+    pub name: String,
+    pub args: Vec<String>,
+    pub kwargs: BTreeMap<String, String>,
+    // markdown between `{{< name >}}` and `{{< /name >}}`
+    pub body: Option<String>,
+}
+
+/// Plain text comrak leaves alone, put where a shortcode was
+pub fn placeholder(index: usize) -> String{
+    format!("aopodcastshortcode{}x", index)
+}
+
+/// Byte ranges of the fenced code blocks and the code spans of `markdown`
+fn code_ranges(markdown: &str) -> Vec<Range<usize>>{
+    let mut ranges = Vec::new();
+    // fence character, its length and where the block starts
+    let mut fence: Option<(char, usize, usize)> = None;
+    let mut text_start = 0;
+    let mut position = 0;
+    for line in markdown.split_inclusive('\n'){
+        let trimmed = line.trim_start_matches(' ');
+        let marker = trimmed.chars().next()
+            .filter(|c| (*c == '`' || *c == '~') && line.len() - trimmed.len() < 4)
+            .map(|c| (c, trimmed.chars().take_while(|other| *other == c).count()))
+            .filter(|(_, length)| *length >= 3);
+        match (fence, marker){
+            (None, Some((c, length))) => {
+                ranges.extend(code_spans(&markdown[text_start..position], text_start));
+                fence = Some((c, length, position));
+            },
+            (Some((c, length, start)), Some((other, other_length)))
+                if c == other && other_length >= length && trimmed[other_length..].trim().is_empty() => {
+                ranges.push(start..position + line.len());
+                fence = None;
+                text_start = position + line.len();
+            },
+            _ => {},
+        }
+        position += line.len();
+    }
+    match fence{
+        // an unclosed fence runs to the end
+        Some((_, _, start)) => ranges.push(start..markdown.len()),
+        None => ranges.extend(code_spans(&markdown[text_start..], text_start)),
+    }
+    ranges
+}
+
+/// Code spans of `text`: a run of backticks up to the next run as long
+fn code_spans(text: &str, offset: usize) -> Vec<Range<usize>>{
+    let mut spans = Vec::new();
+    let bytes = text.as_bytes();
+    let run = |from: usize| bytes[from..].iter().take_while(|byte| **byte == b'`').count();
+    let mut index = 0;
+    while index < bytes.len(){
+        if bytes[index] != b'`'{
+            index += 1;
+            continue;
+        }
+        let length = run(index);
+        let mut end = index + length;
+        let mut closed = None;
+        while end < bytes.len(){
+            if bytes[end] == b'`'{
+                let other = run(end);
+                if other == length{
+                    closed = Some(end + other);
+                    break;
+                }
+                end += other;
+            }else{
+                end += 1;
+            }
+        }
+        match closed{
+            Some(close) => {
+                spans.push(offset + index..offset + close);
+                index = close;
+            },
+            None => index += length,
+        }
+    }
+    spans
+}
+
+/// Swaps every shortcode of `markdown` for a placeholder, except those in
+/// code, which are shown as written. Returns the new markdown and the
+/// shortcodes, in order.
+pub fn extract(markdown: &str) -> (String, Vec<Shortcode>){
+    let tag = Regex::new(TAG).unwrap();
+    let arg = Regex::new(ARG).unwrap();
+    let code = code_ranges(markdown);
+    let mut output = String::new();
+    let mut shortcodes = Vec::new();
+    let mut position = 0;
+    while let Some(caps) = tag.captures_at(markdown, position){
+        let whole = caps.get(0).unwrap();
+        output.push_str(&markdown[position..whole.start()]);
+        position = whole.end();
+        let in_code = code.iter().any(|range| range.contains(&whole.start()));
+        if !caps[1].is_empty() || in_code{
+            // closing tag without its opening one
+            output.push_str(whole.as_str());
+            continue;
+        }
+        let mut shortcode = Shortcode{
+            name: caps[2].to_string(),
+            ..Default::default()
+        };
+        for arg in arg.captures_iter(&caps[3]){
+            let value = arg.get(2).or(arg.get(3)).unwrap().as_str().to_string();
+            match arg.get(1){
+                Some(key) => {
+                    shortcode.kwargs.insert(key.as_str().to_string(), value);
+                },
+                None => shortcode.args.push(value),
+            }
+        }
+        let closing = Regex::new(&format!(r"\{{\{{<\s*/{}\s*>\}}\}}", regex::escape(&shortcode.name))).unwrap();
+        let end = closing.find_iter(&markdown[position..])
+            .map(|end| position + end.start()..position + end.end())
+            .find(|end| !code.iter().any(|range| range.contains(&end.start)));
+        if let Some(end) = end{
+            shortcode.body = Some(markdown[position..end.start].trim().to_string());
+            position = end.end;
+        }
+        output.push_str(&placeholder(shortcodes.len()));
+        shortcodes.push(shortcode);
+    }
+    output.push_str(&markdown[position..]);
+    (output, shortcodes)
+}
+
+/// Puts the rendered shortcodes back in the html. One alone in a line is not
+/// left inside a paragraph.
+pub fn restore(html: &str, rendered: &[String]) -> String{
+    let mut html = html.to_string();
+    for (index, content) in rendered.iter().enumerate(){
+        let placeholder = placeholder(index);
+        html = html
+            .replace(&format!("<p>{}</p>", placeholder), content)
+            .replace(&placeholder, content);
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, restore, code_ranges};
+
+    #[test]
+    fn test_extract(){
+        let markdown = "Mirad:\n\n{{< youtube dQw4w9WgXcQ >}}\n\nY {{< episode papa-friki-1 start=\"12:30\" >}}.\n\n{{< callout type=warning >}}\n**Ojo** con Docker\n{{< /callout >}}\n";
+        let (output, shortcodes) = extract(markdown);
+        assert_eq!(output, "Mirad:\n\naopodcastshortcode0x\n\nY aopodcastshortcode1x.\n\naopodcastshortcode2x\n");
+        assert_eq!(shortcodes[0].name, "youtube");
+        assert_eq!(shortcodes[0].args, vec!["dQw4w9WgXcQ"]);
+        assert_eq!(shortcodes[1].kwargs["start"], "12:30");
+        assert_eq!(shortcodes[2].kwargs["type"], "warning");
+        assert_eq!(shortcodes[2].body.as_deref(), Some("**Ojo** con Docker"));
+    }
+
+    #[test]
+    fn test_restore(){
+        let html = "<p>aopodcastshortcode0x</p>\n<p>Y aopodcastshortcode1x.</p>\n";
+        let rendered = vec!["<iframe></iframe>".to_string(), "<a>1</a>".to_string()];
+        assert_eq!(restore(html, &rendered), "<iframe></iframe>\n<p>Y <a>1</a>.</p>\n");
+    }
+
+    #[test]
+    fn test_no_shortcodes(){
+        let markdown = "Sin {{ llaves }} ni {{< /suelto >}}";
+        assert_eq!(extract(markdown), (markdown.to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn test_code(){
+        let markdown = "Se escribe `{{< youtube id >}}` o ``{{< episode `x` >}}``:\n\n```markdown\n{{< callout >}}\nTexto\n{{< /callout >}}\n```\n\n{{< youtube abc >}}\n";
+        let (output, shortcodes) = extract(markdown);
+        assert_eq!(shortcodes.len(), 1);
+        assert_eq!(shortcodes[0].args, vec!["abc"]);
+        assert_eq!(output, markdown.replace("{{< youtube abc >}}", "aopodcastshortcode0x"));
+        // unclosed fences and backticks
+        let unclosed = "~~~~\n{{< a >}}\n~~~\n";
+        assert_eq!(code_ranges(unclosed), vec![0..unclosed.len()]);
+        assert!(code_ranges("Un ` suelto").is_empty());
+    }
+}
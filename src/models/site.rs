@@ -1,6 +1,8 @@
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
 
+use super::{markdown::Heading, related::PostSummary, chapters::Chapter, person::Credit, transcript::Transcript, utils::is_published};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Site{
     pub author: String,
@@ -21,13 +23,37 @@ pub struct Site{
     pub disqus: String,
     pub youtube: String,
     pub linktree: String,
+    // language of the content, as `es` or `es-ES`
+    #[serde(default = "default_language")]
+    pub language: String,
+    // season new episodes are added to
+    #[serde(default)]
+    pub season: Option<usize>,
+    // slugs of the people in `people/` hosting the show
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+fn default_language() -> String{
+    "es".to_string()
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EpisodeType{
+    #[default]
+    Full,
+    Trailer,
+    Bonus,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum Layout{
     POST,
     PODCAST,
     PAGE,
+    PERSON,
 }
 
 
@@ -37,6 +63,8 @@ pub struct Post{
     pub slug: String,
     pub excerpt: String,
     pub title: String,
+    // html of `source`, filled in once every post can be looked up (see
+    // `markdown::Renderer`)
     pub content: String,
     pub subject: Vec<String>,
     pub date: DateTime<Utc>,
@@ -47,4 +75,27 @@ pub struct Post{
     pub length: u64,
     pub number: usize,
     pub downloads: u64,
+    pub draft: bool,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub season: Option<usize>,
+    pub episode: Option<usize>,
+    pub episode_type: Option<EpisodeType>,
+    pub chapters: Vec<Chapter>,
+    pub transcript: Option<Transcript>,
+    pub people: Vec<Credit>,
+    pub image: Option<String>,
+    pub toc: Vec<Heading>,
+    pub related: Vec<PostSummary>,
+    // older and newer posts of the same layout
+    pub previous: Option<PostSummary>,
+    pub next: Option<PostSummary>,
+    // Markdown `content` comes from
+    #[serde(skip)]
+    pub source: String,
+}
+
+impl Post{
+    pub fn is_published(&self) -> bool{
+        is_published(self.draft, self.publish_at)
+    }
 }
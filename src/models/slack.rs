@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use tracing::info;
+
+use super::{
+    announcer::{Announcer, AnnounceError, render},
+    site::{Post, Site},
+};
+
+/// Options of the Slack channel. The incoming webhook url, a secret, comes
+/// from `SLACK_WEBHOOK`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SlackConfig{
+    pub template: String,
+}
+
+impl Default for SlackConfig{
+    fn default() -> Self{
+        Self{
+            template: "slack.html".to_string(),
+        }
+    }
+}
+
+pub struct Slack{
+    webhook: String,
+    template: String,
+}
+
+impl Slack{
+    pub fn new(webhook: &str) -> Self{
+        Self{
+            webhook: webhook.to_string(),
+            template: SlackConfig::default().template,
+        }
+    }
+
+    pub fn from_config(config: &SlackConfig) -> Option<Self>{
+        let webhook = std::env::var("SLACK_WEBHOOK").ok()?;
+        Some(Self{
+            template: config.template.clone(),
+            ..Self::new(&webhook)
+        })
+    }
+
+    /// Sends `text`, in Slack mrkdwn. Incoming webhooks don't return the id
+    /// of the message, only `ok`.
+    pub async fn send(&self, text: &str) -> Result<(), reqwest::Error>{
+        Client::new()
+            .post(&self.webhook)
+            .json(&json!({
+                "text": text.trim(),
+                "unfurl_links": true,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Announcer for Slack{
+    fn channel(&self) -> &str{
+        "slack"
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        let text = render(&self.template, site, post)?;
+        info!("Slack: {text}");
+        self.send(&text)
+            .await
+            .map_err(|source| AnnounceError::Request{channel: self.channel().to_string(), source})?;
+        // made up, so that `announcements` tells when it was sent
+        Ok(format!("slack-{}-{}", post.identifier, chrono::Utc::now().timestamp()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Slack;
+    use crate::models::{announcer::Announcer, testing::{get_post, get_site}};
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::{method, path}};
+
+    #[tokio::test]
+    async fn test_announce(){
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/services/T0/B0/secreto"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let slack = Slack::new(&format!("{}/services/T0/B0/secreto", server.uri()));
+        let id = slack.announce(&get_site(), &get_post()).await.unwrap();
+        assert!(id.starts_with("slack-pf-1-"), "{}", id);
+        let body: serde_json::Value = server.received_requests().await.unwrap()[0].body_json().unwrap();
+        assert!(body["text"].as_str().unwrap().contains("<https://atareao.es/podcast/papa-friki-1/|Papá Friki 1>"));
+    }
+
+    #[tokio::test]
+    async fn test_error(){
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        let slack = Slack::new(&server.uri());
+        assert!(slack.announce(&get_site(), &get_post()).await.is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeSet;
+
+use super::{data::{Data, Snapshot}, site::{Layout, Post}};
+
+/// Episodes listed as top movers
+const MOVERS: usize = 5;
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 150.0;
+const SPARKLINE_WIDTH: f64 = 120.0;
+const SPARKLINE_HEIGHT: f64 = 24.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpisodeStats{
+    pub identifier: String,
+    pub slug: String,
+    pub title: String,
+    pub number: usize,
+    pub date: DateTime<Utc>,
+    // seconds
+    pub length: u64,
+    // bytes
+    pub size: u64,
+    pub downloads: u64,
+    // since it was published, a day at least
+    pub per_day: f64,
+    // downloads in the last 7 and 30 days, when the history goes that far
+    pub week: Option<u64>,
+    pub month: Option<u64>,
+    // svg sparkline of the history
+    pub chart: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Statistics{
+    pub downloads: u64,
+    pub week: Option<u64>,
+    pub month: Option<u64>,
+    pub chart: String,
+    pub episodes: Vec<EpisodeStats>,
+    // episodes with most downloads in the last week
+    pub movers: Vec<EpisodeStats>,
+}
+
+impl Statistics{
+    pub fn new(posts: &[Post], data: &Data, now: DateTime<Utc>) -> Self{
+        let episodes: Vec<&Post> = posts.iter()
+            .filter(|post| post.layout == Layout::PODCAST)
+            .collect();
+        let episode_stats: Vec<EpisodeStats> = episodes.iter()
+            .map(|post| {
+                let snapshots = data.get_snapshots(&post.identifier);
+                let downloads = snapshots.last()
+                    .map(|snapshot| snapshot.downloads)
+                    .unwrap_or(post.downloads);
+                let days = ((now - post.date).num_seconds() as f64 / 86400.0).max(1.0);
+                EpisodeStats{
+                    identifier: post.identifier.clone(),
+                    slug: post.slug.clone(),
+                    title: post.title.clone(),
+                    number: post.number,
+                    date: post.date,
+                    length: post.length,
+                    size: post.size,
+                    downloads,
+                    per_day: (downloads as f64 / days * 100.0).round() / 100.0,
+                    week: growth(snapshots, downloads, now - Duration::days(7)),
+                    month: growth(snapshots, downloads, now - Duration::days(30)),
+                    chart: chart(&points(snapshots), SPARKLINE_WIDTH, SPARKLINE_HEIGHT),
+                }
+            })
+            .collect();
+        // total of the episodes at every date with a snapshot
+        let dates: BTreeSet<DateTime<Utc>> = episodes.iter()
+            .flat_map(|post| data.get_snapshots(&post.identifier).iter().map(|snapshot| snapshot.date))
+            .collect();
+        let totals: Vec<Snapshot> = dates.into_iter()
+            .map(|date| Snapshot{
+                date,
+                downloads: episodes.iter()
+                    .filter_map(|post| at(data.get_snapshots(&post.identifier), date))
+                    .sum(),
+            })
+            .collect();
+        let downloads = episode_stats.iter().map(|episode| episode.downloads).sum();
+        let mut movers: Vec<EpisodeStats> = episode_stats.iter()
+            .filter(|episode| episode.week.unwrap_or_default() > 0)
+            .cloned()
+            .collect();
+        movers.sort_by_key(|episode| std::cmp::Reverse(episode.week));
+        movers.truncate(MOVERS);
+        Self{
+            downloads,
+            week: total(episode_stats.iter().map(|episode| episode.week)),
+            month: total(episode_stats.iter().map(|episode| episode.month)),
+            chart: chart(&points(&totals), CHART_WIDTH, CHART_HEIGHT),
+            episodes: episode_stats,
+            movers,
+        }
+    }
+
+    pub fn get_rows(&self) -> Vec<Row>{
+        self.episodes.iter().map(Row::from).collect()
+    }
+
+    pub fn to_csv(&self) -> String{
+        let mut csv = String::from("identifier,title,date,duration,size,downloads,downloads_per_day\n");
+        for row in self.get_rows(){
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_field(&row.identifier),
+                csv_field(&row.title),
+                row.date,
+                row.duration,
+                row.size,
+                row.downloads,
+                row.downloads_per_day
+            ));
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error>{
+        serde_json::to_string_pretty(&self.get_rows())
+    }
+}
+
+/// Row of `stats.csv` and `stats.json`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Row{
+    pub identifier: String,
+    pub title: String,
+    pub date: String,
+    pub duration: u64,
+    pub size: u64,
+    pub downloads: u64,
+    pub downloads_per_day: f64,
+}
+
+impl From<&EpisodeStats> for Row{
+    fn from(episode: &EpisodeStats) -> Self{
+        Self{
+            identifier: episode.identifier.clone(),
+            title: episode.title.clone(),
+            date: episode.date.format("%Y-%m-%d").to_string(),
+            duration: episode.length,
+            size: episode.size,
+            downloads: episode.downloads,
+            downloads_per_day: episode.per_day,
+        }
+    }
+}
+
+/// Quoted when it has commas, quotes or line breaks. Text a spreadsheet
+/// would take for a formula gets a leading `'`.
+fn csv_field(value: &str) -> String{
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']){
+        format!("'{}", value)
+    }else{
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']){
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }else{
+        value
+    }
+}
+
+/// Downloads at `date`, from the last snapshot not after it
+fn at(snapshots: &[Snapshot], date: DateTime<Utc>) -> Option<u64>{
+    snapshots.iter()
+        .take_while(|snapshot| snapshot.date <= date)
+        .last()
+        .map(|snapshot| snapshot.downloads)
+}
+
+/// Downloads since `since`, none when there is no snapshot that old
+fn growth(snapshots: &[Snapshot], downloads: u64, since: DateTime<Utc>) -> Option<u64>{
+    at(snapshots, since).map(|before| downloads.saturating_sub(before))
+}
+
+/// Sum of the growth of the episodes with history that old, none when no
+/// episode has it
+fn total(growths: impl Iterator<Item = Option<u64>>) -> Option<u64>{
+    growths.flatten().fold(None, |total, growth| Some(total.unwrap_or_default() + growth))
+}
+
+fn points(snapshots: &[Snapshot]) -> Vec<(i64, u64)>{
+    snapshots.iter()
+        .map(|snapshot| (snapshot.date.timestamp(), snapshot.downloads))
+        .collect()
+}
+
+/// Svg line chart of the points, empty with less than two
+pub fn chart(points: &[(i64, u64)], width: f64, height: f64) -> String{
+    if points.len() < 2{
+        return "".to_string();
+    }
+    let (first, last) = (points[0].0, points[points.len() - 1].0);
+    let min = points.iter().map(|(_, value)| *value).min().unwrap_or_default();
+    let max = points.iter().map(|(_, value)| *value).max().unwrap_or_default();
+    let span_x = (last - first).max(1) as f64;
+    let span_y = (max - min).max(1) as f64;
+    let line = points.iter()
+        .map(|(x, y)| format!(
+            "{:.1},{:.1}",
+            (x - first) as f64 / span_x * width,
+            height - (y - min) as f64 / span_y * height
+        ))
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!(
+        r#"<svg class="chart" viewBox="0 0 {width} {height}" width="{width}" height="{height}" preserveAspectRatio="none" role="img"><title>{min} - {max}</title><polyline fill="none" stroke="currentColor" stroke-width="1.5" points="{line}" /></svg>"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chart, csv_field, Row, Statistics};
+    use crate::models::{data::Data, testing::get_episode, Post};
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn episode(number: usize, downloads: u64) -> Post{
+        get_episode(number, &[
+            ("downloads", &downloads.to_string()),
+            ("number", &number.to_string()),
+            ("size", "54300000"),
+            ("length", "3735"),
+        ])
+    }
+
+    #[test]
+    fn test_statistics(){
+        let now = Utc.with_ymd_and_hms(2024, 6, 30, 12, 0, 0).unwrap();
+        let mut data = Data::default();
+        for (days, first, second) in [(40, 100, 0), (10, 150, 10), (3, 170, 50), (0, 180, 90)]{
+            data.record("pf-1", now - Duration::days(days), first);
+            if second > 0{
+                data.record("pf-2", now - Duration::days(days), second);
+            }
+        }
+        let stats = Statistics::new(&[episode(1, 0), episode(2, 0), episode(3, 7)], &data, now);
+        assert_eq!(stats.downloads, 180 + 90 + 7);
+        assert_eq!(stats.episodes[0].week, Some(30));
+        assert_eq!(stats.episodes[0].month, Some(80));
+        // pf-2 history starts 10 days ago
+        assert_eq!(stats.episodes[1].week, Some(80));
+        assert_eq!(stats.episodes[1].month, None);
+        assert_eq!(stats.episodes[2].week, None);
+        // only episodes with history that old count
+        assert_eq!(stats.week, Some(30 + 80));
+        assert_eq!(stats.month, Some(80));
+        assert_eq!(stats.movers[0].slug, "papa-friki-2");
+        assert_eq!(stats.movers.len(), 2);
+        assert!(stats.chart.starts_with("<svg"));
+        assert!(stats.episodes[2].chart.is_empty());
+    }
+
+    #[test]
+    fn test_export(){
+        let now = Utc.with_ymd_and_hms(2024, 6, 11, 0, 0, 0).unwrap();
+        let mut post = episode(1, 50);
+        post.title = "Docker, \"Pi-hole\" y más".to_string();
+        let stats = Statistics::new(&[post, episode(2, 7)], &Data::default(), now);
+        let rows = stats.get_rows();
+        assert_eq!(rows[0].date, "2024-06-01");
+        assert_eq!(Row::from(&Statistics::new(&[episode(12, 0)], &Data::default(), now).episodes[0]).date, "2024-06-12");
+        assert_eq!(rows[0].downloads_per_day, 5.0);
+        assert_eq!(rows[1].downloads_per_day, 0.78);
+        let csv = stats.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "identifier,title,date,duration,size,downloads,downloads_per_day");
+        assert_eq!(lines[1], r#"pf-1,"Docker, ""Pi-hole"" y más",2024-06-01,3735,54300000,50,5"#);
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json().unwrap()).unwrap();
+        assert_eq!(json[1]["identifier"], "pf-2");
+        assert_eq!(json[1]["duration"], 3735);
+    }
+
+    #[test]
+    fn test_csv_field(){
+        assert_eq!(csv_field("Papá Friki"), "Papá Friki");
+        assert_eq!(csv_field("=HYPERLINK(\"http://x\")"), "\"'=HYPERLINK(\"\"http://x\"\")\"");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-5 trucos"), "'-5 trucos");
+        assert_eq!(csv_field("@atareao"), "'@atareao");
+    }
+
+    #[test]
+    fn test_chart(){
+        let svg = chart(&[(0, 10), (50, 20), (100, 15)], 100.0, 10.0);
+        assert!(svg.contains(r#"points="0.0,10.0 50.0,0.0 100.0,5.0""#), "{}", svg);
+        assert!(chart(&[(0, 10)], 100.0, 10.0).is_empty());
+    }
+}
@@ -1,34 +1,69 @@
+use async_trait::async_trait;
 use reqwest::Client;
-use serde_json::json;
-use tracing::{info, error};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tracing::info;
 
-pub struct Telegram{
-    access_token: String,
-    chat_id: String,
+use super::{
+    announcer::{Announcer, AnnounceError, BoxError, render, get_audio_url, get_image_url},
+    site::{Post, Site},
+};
+
+/// Options of the Telegram channel. The bot token comes from `TELEGRAM_TOKEN`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TelegramConfig{
+    pub template: String,
+    // `TELEGRAM_CHAT_ID` when missing
+    pub chat_id: Option<String>,
+    // send the episode as an audio with the message as caption. Otherwise
+    // the artwork of the episode, if it has its own, goes with the message.
+    // Telegram only shows the cover inside the mp3 next to an audio.
+    pub audio: bool,
 }
 
-pub fn get_telegram_client() -> Option<Telegram>{
-    match std::env::var("TELEGRAM_TOKEN"){
-        Ok(token) => {
-            match std::env::var("TELEGRAM_CHAT_ID"){
-                Ok(chat_id) => Some(Telegram::new(&token, &chat_id)),
-                Err(_) => None,
-            }
-        },
-        Err(_) => None,
+impl Default for TelegramConfig{
+    fn default() -> Self{
+        Self{
+            template: "telegram.html".to_string(),
+            chat_id: None,
+            audio: true,
+        }
     }
 }
 
+pub struct Telegram{
+    access_token: String,
+    chat_id: String,
+    template: String,
+    audio: bool,
+}
+
 impl Telegram{
     pub fn new(access_token: &str, chat_id: &str) -> Self{
         Self{
             access_token: access_token.to_string(),
             chat_id: chat_id.to_string(),
+            template: TelegramConfig::default().template,
+            audio: true,
         }
     }
 
-    #[allow(dead_code)]
-    pub async fn post(&self, message: &str){
+    pub fn from_config(config: &TelegramConfig) -> Option<Self>{
+        let token = std::env::var("TELEGRAM_TOKEN").ok()?;
+        let chat_id = match &config.chat_id{
+            Some(chat_id) => chat_id.to_owned(),
+            None => std::env::var("TELEGRAM_CHAT_ID").ok()?,
+        };
+        Some(Self{
+            template: config.template.clone(),
+            audio: config.audio,
+            ..Self::new(&token, &chat_id)
+        })
+    }
+
+    /// Sends `message` and returns the id of the message
+    pub async fn post(&self, message: &str) -> Result<String, BoxError>{
         info!("Message to publish in Telegram: {}", message);
         let url = format!("https://api.telegram.org/bot{}/sendMessage",
             self.access_token);
@@ -38,23 +73,19 @@ impl Telegram{
             "text": message,
             "parse_mode": "HTML",
         });
-        match Client::new()
+        let answer: Value = Client::new()
             .post(url)
             .json(&message)
             .send()
-            .await{
-                Ok(response) => {
-                    info!("Mensaje envíado a Telegram: {}",
-                        response.status().to_string());
-                },
-                Err(error) => {
-                    error!("No he podido enviar el mensaje a Telegram: {}",
-                        error.to_string());
-                },
-            }
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        message_id(&answer)
     }
 
-    pub async fn send_audio(&self, audio: &str, caption: &str) -> Result<String, reqwest::Error>{
+    /// Sends `audio` with `caption` and returns the id of the message
+    pub async fn send_audio(&self, audio: &str, caption: &str) -> Result<String, BoxError>{
         let url = format!("https://api.telegram.org/bot{}/sendAudio",
             self.access_token);
         info!("url  {}", url);
@@ -66,14 +97,37 @@ impl Telegram{
             "caption": content,
             "parse_mode": "HTML",
         });
-        Client::new()
+        let answer: Value = Client::new()
+            .post(url)
+            .json(&message)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        message_id(&answer)
+    }
+
+    /// Sends the picture at `photo` with `caption` and returns the id of the
+    /// message
+    pub async fn send_photo(&self, photo: &str, caption: &str) -> Result<String, BoxError>{
+        let url = format!("https://api.telegram.org/bot{}/sendPhoto",
+            self.access_token);
+        let message = json!({
+            "chat_id": self.chat_id,
+            "photo": photo,
+            "caption": Self::prepare(caption),
+            "parse_mode": "HTML",
+        });
+        let answer: Value = Client::new()
             .post(url)
             .json(&message)
             .send()
             .await?
             .error_for_status()?
-            .text()
-            .await
+            .json()
+            .await?;
+        message_id(&answer)
     }
 
     fn prepare(text: &str) -> String{
@@ -86,11 +140,36 @@ impl Telegram{
     }
 }
 
+/// Id of the message the Bot API answered with
+fn message_id(answer: &Value) -> Result<String, BoxError>{
+    answer["result"]["message_id"].as_i64()
+        .map(|id| id.to_string())
+        .ok_or_else(|| format!("Telegram answered without the id of the message: {}", answer).into())
+}
+
+#[async_trait]
+impl Announcer for Telegram{
+    fn channel(&self) -> &str{
+        "telegram"
+    }
+
+    async fn announce(&self, site: &Site, post: &Post) -> Result<String, AnnounceError>{
+        let caption = render(&self.template, site, post)?;
+        info!("Caption: {caption}");
+        let result = match (self.audio, get_image_url(site, post)){
+            (true, _) => self.send_audio(&get_audio_url(post), &caption).await,
+            (false, Some(image)) => self.send_photo(&image, &caption).await,
+            (false, None) => self.post(&Self::prepare(&caption)).await,
+        };
+        result.map_err(|source| AnnounceError::Send{channel: self.channel().to_string(), source})
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use dotenv::dotenv;
     use std::env;
-    use crate::models::telegram::Telegram;
+    use crate::models::telegram::{message_id, Telegram};
     use tokio;
 
     #[tokio::test]
@@ -111,5 +190,12 @@ Ya sabéis, poco a poco irá llegando a vuestro programa de podcast favorito, a
         let answer = telegram.send_audio(&audio, caption).await;
         println!("{:?}", answer);
     }
+
+    #[test]
+    fn test_message_id(){
+        let answer = serde_json::json!({"ok": true, "result": {"message_id": 42}});
+        assert_eq!(message_id(&answer).unwrap(), "42");
+        assert!(message_id(&serde_json::json!({"ok": true, "result": true})).is_err());
+    }
 }
 
@@ -0,0 +1,68 @@
+//! Site and posts the tests of the models share
+
+use chrono::{Duration, TimeZone, Utc};
+use std::collections::BTreeMap;
+
+use super::{
+    content::{Document, Schema},
+    episode,
+    markdown::to_html,
+    site::{Post, Site},
+};
+
+pub fn get_site() -> Site{
+    serde_yaml::from_str("author: Papá Friki
+title: Papá Friki
+description: Podcast
+podcast_feed: feed
+baseurl: podcast
+url: https://atareao.es
+avatar: /avatar.png
+category: Technology
+subcategory: Podcasting
+explicit: false
+email: ''
+gitlab: ''
+rss: ''
+twitter: ''
+google_analytics: ''
+disqus: ''
+youtube: ''
+linktree: ''").unwrap()
+}
+
+/// Post of the Markdown source `data` of a content type, rendered
+pub fn parse<S: Schema>(data: &str) -> Post{
+    let path = format!("{}/test.md", S::DIR);
+    let (document, _) = Document::<S>::parse(&path, data).unwrap();
+    let mut post = document.get_post();
+    post.content = to_html(&post.source);
+    post
+}
+
+/// Episode `pf-<number>`, published `number - 1` days after 2024-06-01.
+/// `fields` replace or add front matter keys.
+pub fn get_episode(number: usize, fields: &[(&str, &str)]) -> Post{
+    let date = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap() + Duration::days(number as i64 - 1);
+    let mut front_matter = BTreeMap::from([
+        ("identifier", format!("pf-{}", number)),
+        ("title", format!("Papá Friki {}", number)),
+        ("date", date.format("%Y-%m-%d").to_string()),
+        ("downloads", "10".to_string()),
+        ("filename", format!("pf-{}.mp3", number)),
+        ("size", "100".to_string()),
+        ("length", "60".to_string()),
+    ]);
+    for (key, value) in fields{
+        front_matter.insert(key, value.to_string());
+    }
+    let lines: String = front_matter.iter()
+        .map(|(key, value)| format!("{}: {}\n", key, value))
+        .collect();
+    parse::<episode::Metadata>(&format!("---\n{}---\nDescripción", lines))
+}
+
+/// The first episode
+pub fn get_post() -> Post{
+    get_episode(1, &[])
+}
@@ -0,0 +1,121 @@
+use minijinja::{Error, ErrorKind};
+use once_cell::sync::OnceCell;
+use std::io;
+use tracing::debug;
+
+/// Templates here override the ones of the theme
+pub const LOCAL_DIR: &str = "templates";
+pub const THEMES_DIR: &str = "themes";
+pub const DEFAULT_THEME: &str = "default";
+
+/// Theme chosen in the configuration. Set it before the first render.
+pub static THEME: OnceCell<String> = OnceCell::new();
+
+macro_rules! embed {
+    ($($name:literal),* $(,)?) => {
+        &[$(($name, include_str!(concat!("../../themes/default/", $name)))),*]
+    };
+}
+
+/// The default theme, built into the binary
+const EMBEDDED: &[(&str, &str)] = embed!(
+    "404.html",
+    "analytics.html",
+    "bluesky.html",
+    "default.html",
+    "discord.html",
+    "disqus.html",
+    "email.html",
+    "feed.xml",
+    "index.html",
+    "mastodon.html",
+    "matrix.html",
+    "meta.html",
+    "page.html",
+    "people.html",
+    "person.html",
+    "post.html",
+    "search.html",
+    "season.html",
+    "shortcodes/callout.html",
+    "shortcodes/episode.html",
+    "shortcodes/youtube.html",
+    "slack.html",
+    "statistics.html",
+    "svg-icons.html",
+    "telegram.html",
+);
+
+/// Looks up `name` in `templates/`, then in `themes/<theme>/` and at last in
+/// the embedded default theme
+pub fn load(name: &str) -> Result<Option<String>, Error> {
+    if name.split(['/', '\\']).any(|segment| segment == ".." || segment.is_empty()) {
+        return Ok(None);
+    }
+    let theme = THEME.get().map(|theme| theme.as_str()).unwrap_or(DEFAULT_THEME);
+    for dir in [LOCAL_DIR.to_string(), format!("{}/{}", THEMES_DIR, theme)] {
+        let path = format!("{}/{}", dir, name);
+        match std::fs::read_to_string(&path) {
+            Ok(source) => {
+                debug!("Template {}", path);
+                return Ok(Some(source));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("could not read template {}", path)
+                ).with_source(err));
+            }
+        }
+    }
+    Ok(get_embedded(name).map(|source| source.to_string()))
+}
+
+pub fn get_embedded(name: &str) -> Option<&'static str> {
+    EMBEDDED.iter()
+        .find(|(embedded, _)| *embedded == name)
+        .map(|(_, source)| *source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_embedded, load, EMBEDDED, THEMES_DIR, DEFAULT_THEME};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_embedded(){
+        assert!(get_embedded("feed.xml").unwrap().contains("<rss"));
+        assert!(get_embedded("nope.html").is_none());
+    }
+
+    #[test]
+    fn test_embedded_is_complete(){
+        // a file added to the default theme but not to `EMBEDDED` would only
+        // be missing from the binary
+        let root = PathBuf::from(THEMES_DIR).join(DEFAULT_THEME);
+        let mut files = Vec::new();
+        let mut pending = vec![root.clone()];
+        while let Some(dir) = pending.pop(){
+            for entry in std::fs::read_dir(dir).unwrap(){
+                let path = entry.unwrap().path();
+                if path.is_dir(){
+                    pending.push(path);
+                }else{
+                    let name = path.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/");
+                    files.push(name);
+                }
+            }
+        }
+        files.sort();
+        let embedded: Vec<&str> = EMBEDDED.iter().map(|(name, _)| *name).collect();
+        assert_eq!(files, embedded);
+    }
+
+    #[test]
+    fn test_load(){
+        assert!(load("post.html").unwrap().unwrap().contains("post.title"));
+        assert!(load("../Cargo.toml").unwrap().is_none());
+        assert!(load("nope.html").unwrap().is_none());
+    }
+}
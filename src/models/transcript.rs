@@ -0,0 +1,142 @@
+use serde::{Serialize, Deserialize};
+use regex::Regex;
+use tracing::debug;
+
+use super::chapters::format_timestamp;
+
+/// Extensions looked up next to `episodes/<identifier>.md`, in order
+pub const EXTENSIONS: [&str; 2] = ["vtt", "srt"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Cue{
+    pub start: f64,
+    pub end: f64,
+    pub time: String,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Transcript{
+    // name it is published with, next to the episode page
+    pub filename: String,
+    pub mime: String,
+    pub cues: Vec<Cue>,
+    #[serde(skip)]
+    pub source: String,
+}
+
+impl Transcript{
+    pub fn new(extension: &str, source: &str) -> Self{
+        let mime = match extension{
+            "srt" => "application/srt",
+            _ => "text/vtt",
+        };
+        Self{
+            filename: format!("transcript.{}", extension),
+            mime: mime.to_string(),
+            cues: parse_cues(source),
+            source: source.to_string(),
+        }
+    }
+
+    /// Looks for `<identifier>.vtt` or `<identifier>.srt` in `dir`
+    pub async fn read(dir: &str, identifier: &str) -> Option<Self>{
+        for extension in EXTENSIONS{
+            let filename = format!("{}/{}.{}", dir, identifier, extension);
+            if let Ok(source) = tokio::fs::read_to_string(&filename).await{
+                debug!("Transcript {}", &filename);
+                return Some(Self::new(extension, &source));
+            }
+        }
+        None
+    }
+
+    /// Whether `dir` has a transcript of `identifier`
+    pub async fn exists(dir: &str, identifier: &str) -> bool{
+        for extension in EXTENSIONS{
+            let filename = format!("{}/{}.{}", dir, identifier, extension);
+            if tokio::fs::metadata(&filename).await.is_ok(){
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Cues of a WebVTT or SubRip file. Both are blocks separated by blank lines
+/// with a `start --> end` timing line; anything before it is an identifier.
+pub fn parse_cues(source: &str) -> Vec<Cue>{
+    let tags = Regex::new(r"<[^>]*>").unwrap();
+    let voice = Regex::new(r"<v(?:\.[^ >]*)? ([^>]+)>").unwrap();
+    let source = source.replace("\r\n", "\n");
+    let mut cues = Vec::new();
+    for block in source.split("\n\n"){
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let timing = match lines.next(){
+            Some(timing) => timing,
+            None => continue,
+        };
+        let mut times = timing.split("-->");
+        let start = times.next().and_then(parse_time);
+        let end = times.next()
+            .and_then(|end| end.split_whitespace().next())
+            .and_then(parse_time);
+        let (start, end) = match (start, end){
+            (Some(start), Some(end)) => (start, end),
+            _ => continue,
+        };
+        let text = lines
+            .map(|line| {
+                let line = voice.replace_all(line, "$1: ");
+                tags.replace_all(&line, "").trim().to_string()
+            })
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<String>>()
+            .join(" ");
+        if !text.is_empty(){
+            cues.push(Cue{
+                start,
+                end,
+                time: format_timestamp(start),
+                text: html_escape::decode_html_entities(&text).to_string(),
+            });
+        }
+    }
+    cues
+}
+
+/// `00:01:02.500`, `00:01:02,500` or `01:02.500` to seconds
+fn parse_time(value: &str) -> Option<f64>{
+    let value = value.trim().replace(',', ".");
+    let mut seconds = 0.0;
+    for part in value.split(':'){
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cues, Transcript};
+
+    #[test]
+    fn test_vtt(){
+        let vtt = "WEBVTT\n\nNOTE generado\n\n1\n00:00:01.000 --> 00:00:04.000 align:start\n<v Papá Friki>Buenas muchachada</v>\n\n01:02.500 --> 01:05.000\nHoy hablamos de <b>Pi-hole</b>\ny de Docker\n";
+        let cues = parse_cues(vtt);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "Papá Friki: Buenas muchachada");
+        assert_eq!(cues[0].end, 4.0);
+        assert_eq!(cues[1].start, 62.5);
+        assert_eq!(cues[1].time, "00:01:02");
+        assert_eq!(cues[1].text, "Hoy hablamos de Pi-hole y de Docker");
+    }
+
+    #[test]
+    fn test_srt(){
+        let srt = "1\r\n00:00:01,000 --> 00:00:04,000\r\nBuenas muchachada\r\n\r\n2\r\n00:12:30,000 --> 00:12:35,000\r\nPi-hole\r\n";
+        let transcript = Transcript::new("srt", srt);
+        assert_eq!(transcript.mime, "application/srt");
+        assert_eq!(transcript.filename, "transcript.srt");
+        assert_eq!(transcript.cues[1].start, 750.0);
+    }
+}
@@ -1,6 +1,8 @@
 use regex::Regex;
-use chrono::{offset::TimeZone, DateTime, NaiveDate, NaiveTime, Utc};
+use chrono::{offset::TimeZone, DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{de, Deserialize, Deserializer};
 use tracing::debug;
+use std::{fmt, marker::PhantomData};
 
 const EXCERPT_LENGTH: usize = 55;
 
@@ -65,6 +67,7 @@ pub fn get_first_words(content: &str, number: usize) -> &str{
     }
 }
 
+#[cfg(test)]
 pub fn get_unix_time(ymd: &str) -> DateTime<Utc>{
     let nd = NaiveDate::parse_from_str(ymd, "%Y-%m-%d").unwrap();
     let nt = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
@@ -72,6 +75,87 @@ pub fn get_unix_time(ymd: &str) -> DateTime<Utc>{
     TimeZone::from_utc_datetime(&Utc, &ndt)
 }
 
+/// Parses RFC 3339 datetimes as well as `YYYY-MM-DD HH:MM[:SS]` and plain
+/// `YYYY-MM-DD` dates. Values without offset are taken as UTC.
+pub fn parse_datetime(value: &str) -> Option<DateTime<Utc>>{
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value){
+        return Some(datetime.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]{
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(value, format){
+            return Some(TimeZone::from_utc_datetime(&Utc, &datetime));
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|date| TimeZone::from_utc_datetime(&Utc, &date.and_time(NaiveTime::MIN)))
+}
+
+pub fn deserialize_option_datetime<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where D: Deserializer<'de>{
+    match Option::<String>::deserialize(deserializer)?{
+        Some(value) if !value.trim().is_empty() => match parse_datetime(&value){
+            Some(datetime) => Ok(Some(datetime)),
+            None => Err(serde::de::Error::custom(format!("invalid datetime `{}`", value))),
+        },
+        _ => Ok(None),
+    }
+}
+
+pub fn string_or_seq_string<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where D: Deserializer<'de>
+{
+    struct StringOrVec(PhantomData<Vec<String>>);
+
+    impl<'de> de::Visitor<'de> for StringOrVec {
+        type Value = Vec<String>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("string or list of strings")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where E: de::Error
+        {
+            Ok(vec![value.to_owned()])
+        }
+
+        fn visit_seq<S>(self, visitor: S) -> Result<Self::Value, S::Error>
+            where S: de::SeqAccess<'de>
+        {
+            Deserialize::deserialize(de::value::SeqAccessDeserializer::new(visitor))
+        }
+    }
+
+    deserializer.deserialize_any(StringOrVec(PhantomData))
+}
+
+/// Drafts are never published; scheduled content waits for `publish_at`.
+pub fn is_published(draft: bool, publish_at: Option<DateTime<Utc>>) -> bool{
+    !draft && publish_at.map_or(true, |publish_at| publish_at <= Utc::now())
+}
+
+#[test]
+fn test_parse_datetime(){
+    let expected = get_unix_time("2024-05-15");
+    assert_eq!(parse_datetime("2024-05-15"), Some(expected));
+    assert_eq!(parse_datetime("2024-05-15T00:00:00Z"), Some(expected));
+    assert_eq!(parse_datetime("2024-05-15T02:00:00+02:00"), Some(expected));
+    assert_eq!(parse_datetime("2024-05-15 00:00"), Some(expected));
+    assert_eq!(parse_datetime("15/05/2024"), None);
+}
+
+#[test]
+fn test_is_published(){
+    let past = Some(get_unix_time("2000-01-01"));
+    let future = Some(Utc::now() + chrono::Duration::days(1));
+    assert!(is_published(false, None));
+    assert!(is_published(false, past));
+    assert!(!is_published(false, future));
+    assert!(!is_published(true, past));
+}
+
 #[test]
 fn test_get_first_words(){
     let content = "En un lugar de la Mancha, de cuyo nombre no quiero acordarme, no ha mucho tiempo que vivía un hidalgo de los de lanza en astillero, adarga antigua, rocín flaco y galgo corredor. Una olla de algo más vaca que carnero, salpicón las más noches, duelos y quebrantos los sábados, lantejas los viernes, algún palomino de añadidura los domingos, consumían las tres partes de su hacienda. El resto della concluían sayo de velarte, calzas de velludo para las fiestas, con sus pantuflos de lo mesmo, y los días de entresemana se honraba con su vellorí de lo más fino. Tenía en su casa una ama que pasaba de los cuarenta, y una sobrina que no llegaba a los veinte, y un mozo de campo y plaza, que así ensillaba el rocín como tomaba la podadera. Frisaba la edad de nuestro hidalgo con los cincuenta años; era de complexión recia, seco de carnes, enjuto de rostro, gran madrugador y amigo de la caza. Quieren decir que tenía el sobrenombre de Quijada, o Quesada, que en esto hay alguna diferencia en los autores que deste caso escriben; aunque por conjeturas verosímiles se deja entender que se llamaba Quijana. Pero esto importa poco a nuestro cuento: basta que en la narración dél no se salga un punto de la verdad.";
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, StatusCode};
+use serde::{Serialize, Deserialize};
+use sha2::Sha256;
+use std::{fmt::{self, Display}, time::Duration};
+use tracing::{debug, info, error};
+
+use super::site::Post;
+
+/// Header with the HMAC-SHA256 of the body, as `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "X-Aopodcast-Signature";
+pub const EVENT_HEADER: &str = "X-Aopodcast-Event";
+/// First wait between attempts, doubled every retry
+const RETRY_DELAY_MILLIS: u64 = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Event{
+    #[serde(rename = "episode.new")]
+    EpisodeNew,
+    #[serde(rename = "episode.updated")]
+    EpisodeUpdated,
+    #[serde(rename = "build.finished")]
+    BuildFinished,
+}
+
+impl Display for Event{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            Event::EpisodeNew => write!(f, "episode.new"),
+            Event::EpisodeUpdated => write!(f, "episode.updated"),
+            Event::BuildFinished => write!(f, "build.finished"),
+        }
+    }
+}
+
+/// Entry of the `webhooks` section of the configuration
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Webhook{
+    pub url: String,
+    // every event when empty
+    #[serde(default)]
+    pub events: Vec<Event>,
+    // environment variable with the key the payload is signed with
+    #[serde(default)]
+    pub secret: Option<String>,
+    // seconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_timeout() -> u64{
+    10
+}
+
+fn default_retries() -> u32{
+    3
+}
+
+/// Body of the request
+#[derive(Debug, Serialize)]
+pub struct Payload<'a>{
+    pub event: Event,
+    pub site_url: &'a str,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post: Option<&'a Post>,
+}
+
+#[derive(Debug)]
+pub enum WebhookError{
+    Request{url: String, source: reqwest::Error},
+    Status{url: String, status: StatusCode},
+}
+
+impl Display for WebhookError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            WebhookError::Request{url, ..} => write!(f, "Can not call {}", url),
+            WebhookError::Status{url, status} => write!(f, "{} answered {}", url, status),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self{
+            WebhookError::Request{source, ..} => Some(source),
+            WebhookError::Status{..} => None,
+        }
+    }
+}
+
+impl Webhook{
+    pub fn accepts(&self, event: Event) -> bool{
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    /// Posts `body`, retrying timeouts, connection errors and 5xx or 429
+    /// answers with a growing delay
+    pub async fn deliver(&self, event: Event, body: &str) -> Result<(), WebhookError>{
+        let client = Client::builder()
+            .timeout(Duration::from_secs(self.timeout))
+            .build()
+            .map_err(|source| WebhookError::Request{url: self.url.clone(), source})?;
+        let signature = self.secret.as_ref()
+            .and_then(|name| std::env::var(name).ok())
+            .map(|secret| sign(&secret, body));
+        let mut attempt = 0;
+        loop{
+            let mut request = client.post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event.to_string())
+                .body(body.to_string());
+            if let Some(signature) = &signature{
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+            let error = match request.send().await{
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    let error = WebhookError::Status{url: self.url.clone(), status};
+                    if !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS{
+                        return Err(error);
+                    }
+                    error
+                },
+                Err(source) => WebhookError::Request{url: self.url.clone(), source},
+            };
+            if attempt >= self.retries{
+                return Err(error);
+            }
+            attempt += 1;
+            debug!("{}. Retry {} of {}", error, attempt, self.retries);
+            tokio::time::sleep(Duration::from_millis(RETRY_DELAY_MILLIS << (attempt - 1).min(10))).await;
+        }
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` with `secret`
+pub fn sign(secret: &str, body: &str) -> String{
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC takes keys of any size");
+    mac.update(body.as_bytes());
+    let hash: String = mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hash)
+}
+
+/// Calls every webhook interested in `event`. Failures are only logged.
+pub async fn notify(webhooks: &[Webhook], event: Event, site_url: &str, post: Option<&Post>){
+    let payload = Payload{
+        event,
+        site_url,
+        timestamp: Utc::now(),
+        post,
+    };
+    let body = match serde_json::to_string(&payload){
+        Ok(body) => body,
+        Err(err) => {
+            error!("Can not serialize {} payload. {:#}", event, err);
+            return;
+        }
+    };
+    for webhook in webhooks.iter().filter(|webhook| webhook.accepts(event)){
+        match webhook.deliver(event, &body).await{
+            Ok(_) => info!("Webhook {} called for {}", webhook.url, event),
+            Err(err) => {
+                error!("Webhook failed. {:#}", err);
+                // render causes as well
+                let mut err = &err as &dyn std::error::Error;
+                while let Some(next_err) = err.source() {
+                    error!("caused by: {:#}", next_err);
+                    err = next_err;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, Event, Webhook, SIGNATURE_HEADER, EVENT_HEADER};
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::{method, path, header}};
+
+    fn get_webhook(url: &str) -> Webhook{
+        serde_yaml::from_str(&format!("url: {}\nsecret: AOPODCAST_TEST_WEBHOOK_SECRET\nretries: 1\ntimeout: 1", url)).unwrap()
+    }
+
+    #[test]
+    fn test_sign(){
+        // RFC 4231, test case 2
+        assert_eq!(sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn test_accepts(){
+        let mut webhook = get_webhook("http://localhost");
+        assert!(webhook.accepts(Event::BuildFinished));
+        webhook = serde_yaml::from_str("url: http://localhost\nevents: [episode.new]").unwrap();
+        assert!(webhook.accepts(Event::EpisodeNew));
+        assert!(!webhook.accepts(Event::EpisodeUpdated));
+        assert_eq!(webhook.timeout, 10);
+        assert_eq!(webhook.retries, 3);
+    }
+
+    #[tokio::test]
+    async fn test_deliver(){
+        std::env::set_var("AOPODCAST_TEST_WEBHOOK_SECRET", "secreto");
+        let server = MockServer::start().await;
+        let body = r#"{"event":"build.finished"}"#;
+        // the first attempt fails, the retry gets through
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header(EVENT_HEADER, "build.finished"))
+            .and(header(SIGNATURE_HEADER, sign("secreto", body).as_str()))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let webhook = get_webhook(&format!("{}/hook", server.uri()));
+        assert!(webhook.deliver(Event::BuildFinished, body).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_deliver_errors(){
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/gone"))
+            .respond_with(ResponseTemplate::new(410))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/slow"))
+            .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(3)))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let webhook = get_webhook(&format!("{}/gone", server.uri()));
+        assert!(webhook.deliver(Event::EpisodeNew, "{}").await.is_err());
+        let webhook = get_webhook(&format!("{}/slow", server.uri()));
+        assert!(webhook.deliver(Event::EpisodeNew, "{}").await.is_err());
+    }
+}